@@ -0,0 +1,21 @@
+use std::process::Command;
+
+// Embeds the git commit hash and build timestamp so bug reports can identify the exact build
+fn main() {
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=LIVEFRAME_GIT_HASH={}", git_hash);
+
+    let build_timestamp = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC");
+    println!("cargo:rustc-env=LIVEFRAME_BUILD_TIMESTAMP={}", build_timestamp);
+
+    // Rebuild when HEAD moves so the hash stays accurate
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
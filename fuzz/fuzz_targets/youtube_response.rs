@@ -0,0 +1,15 @@
+// Feeds arbitrary input to the liveBroadcasts response parser, which must reject malformed
+// responses with an error instead of panicking
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/models.rs"]
+mod models;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = serde_json::from_str::<models::LiveBroadcastsResponse>(text);
+    }
+});
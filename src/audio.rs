@@ -8,11 +8,10 @@ use log::{error, info, warn};
 use tokio::io::AsyncReadExt;
 use tokio::process::Command;
 use tokio_util::sync::CancellationToken;
-use windows::Win32::Foundation::HWND;
 use yt_dlp::client::deps::Libraries;
 use yt_dlp::Downloader;
 
-use crate::window::{set_color_state, COLOR_GREEN, COLOR_RED};
+use crate::window::{set_color_state, WindowHandle, COLOR_AUDIBLE, COLOR_SILENT};
 
 const SAMPLE_RATE: u32 = 48_000;
 const CHANNELS: u32 = 2;
@@ -23,15 +22,9 @@ const BYTES_PER_FRAME: usize = (CHANNELS as usize) * 4;
 const SILENCE_THRESHOLD_LUFS: f64 = -50.0;
 // How often we sample momentary loudness and make a color decision.
 const EVAL_INTERVAL: Duration = Duration::from_millis(100);
-// Green -> Red requires this many consecutive silent evaluations (~500ms).
+// Audible -> Silent requires this many consecutive silent evaluations (~500ms).
 const SILENCE_EVALS_TO_RED: u32 = 5;
 
-// HWND is not Send; wrap it so we can move it into the spawned audio task.
-#[derive(Clone, Copy)]
-pub struct SendHwnd(pub HWND);
-unsafe impl Send for SendHwnd {}
-unsafe impl Sync for SendHwnd {}
-
 fn libs_dir() -> anyhow::Result<PathBuf> {
     let home = dirs::home_dir().ok_or_else(|| anyhow!("no home dir"))?;
     Ok(home.join(".liveframe").join("libs"))
@@ -41,7 +34,7 @@ fn libs_dir() -> anyhow::Result<PathBuf> {
 // measuring momentary loudness and updating the window frame color.
 pub async fn run_audio_task(
     video_id: String,
-    hwnd: SendHwnd,
+    window: WindowHandle,
     cancel: CancellationToken,
 ) -> anyhow::Result<()> {
     let libs = libs_dir()?;
@@ -184,11 +177,11 @@ pub async fn run_audio_task(
                             let audible = lufs > SILENCE_THRESHOLD_LUFS && lufs.is_finite();
                             if audible {
                                 silent_evals = 0;
-                                set_color_state(hwnd.0, COLOR_GREEN);
+                                set_color_state(&window, COLOR_AUDIBLE);
                             } else {
                                 silent_evals = silent_evals.saturating_add(1);
                                 if silent_evals >= SILENCE_EVALS_TO_RED {
-                                    set_color_state(hwnd.0, COLOR_RED);
+                                    set_color_state(&window, COLOR_SILENT);
                                 }
                             }
                         }
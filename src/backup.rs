@@ -0,0 +1,110 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use log::{info, warn};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::config::{get_config_path, get_liveframe_dir, write_atomic};
+
+// Name of the config file inside the archive, wherever the config is loaded from
+const CONFIG_ENTRY: &str = "config.toml";
+
+// Files of ~/.liveframe that are backed up, besides the config. The token (token.json) and any
+// pending authorization are left out, so an archive never grants access to the account.
+const DATA_ENTRIES: &[&str] = &["secret.json", "stats.json"];
+
+// Writes the config, client secrets and stats into a timestamped zip archive in `output` (a
+// directory, or the archive path) or the current directory, and returns the archive path
+pub fn backup_config(
+    config_path: Option<&Path>,
+    output: Option<&Path>,
+) -> std::result::Result<PathBuf, Box<dyn std::error::Error>> {
+    let file_name = format!(
+        "liveframe-backup-{}.zip",
+        Local::now().format("%Y%m%d-%H%M%S")
+    );
+    let archive_path = match output {
+        Some(output) if output.is_dir() => output.join(file_name),
+        Some(output) => output.to_path_buf(),
+        None => PathBuf::from(file_name),
+    };
+
+    let mut archive = ZipWriter::new(File::create(&archive_path)?);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let liveframe_dir = get_liveframe_dir()?;
+    for (entry, path) in backup_sources(config_path, &liveframe_dir)? {
+        if !path.exists() {
+            info!("Skipping {} ({} doesn't exist)", entry, path.display());
+            continue;
+        }
+        archive.start_file(entry, options)?;
+        archive.write_all(&fs::read(&path)?)?;
+        info!("Added {} from {}", entry, path.display());
+    }
+    archive.finish()?;
+
+    Ok(archive_path)
+}
+
+// Restores the files of an archive made by backup_config, keeping each replaced file as
+// <name>.bak. Entries other than the backed-up files are ignored.
+pub fn restore_config(
+    config_path: Option<&Path>,
+    archive_path: &Path,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let mut archive = ZipArchive::new(File::open(archive_path)?)?;
+    let liveframe_dir = get_liveframe_dir()?;
+    let sources = backup_sources(config_path, &liveframe_dir)?;
+    fs::create_dir_all(&liveframe_dir)?;
+
+    let mut restored = 0;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let Some((entry, path)) = sources.iter().find(|(entry, _)| *entry == file.name()) else {
+            warn!("Ignoring unexpected archive entry {}", file.name());
+            continue;
+        };
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        if path.exists() {
+            let mut backup = path.clone().into_os_string();
+            backup.push(".bak");
+            fs::rename(path, &backup)?;
+            info!(
+                "Kept the previous {} as {}",
+                entry,
+                PathBuf::from(backup).display()
+            );
+        }
+        write_atomic(path, &contents)?;
+        info!("Restored {} to {}", entry, path.display());
+        restored += 1;
+    }
+
+    if restored == 0 {
+        return Err(format!("{} contains no liveframe files", archive_path.display()).into());
+    }
+    Ok(())
+}
+
+// Returns the archive entry name and local path of each backed-up file
+fn backup_sources(
+    config_path: Option<&Path>,
+    liveframe_dir: &Path,
+) -> std::result::Result<Vec<(&'static str, PathBuf)>, Box<dyn std::error::Error>> {
+    let config_path = match config_path {
+        Some(path) => path.to_path_buf(),
+        None => get_config_path()?,
+    };
+    let mut sources = vec![(CONFIG_ENTRY, config_path)];
+    sources.extend(
+        DATA_ENTRIES
+            .iter()
+            .map(|&entry| (entry, liveframe_dir.join(entry))),
+    );
+    Ok(sources)
+}
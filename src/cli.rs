@@ -0,0 +1,197 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+// Version with the git commit hash and build timestamp embedded by build.rs
+pub const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("LIVEFRAME_GIT_HASH"),
+    ", built ",
+    env!("LIVEFRAME_BUILD_TIMESTAMP"),
+    ")"
+);
+
+// Command line arguments. Running without a subcommand starts the frame as usual.
+#[derive(Parser)]
+#[command(
+    name = "liveframe",
+    version,
+    long_version = LONG_VERSION,
+    about = "Displays a frame around your screen based on your YouTube streaming state"
+)]
+pub struct Cli {
+    /// Config file to use instead of ~/.liveframe/config.toml
+    #[arg(long, global = true, env = "LIVEFRAME_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Only log warnings and errors, overriding log_level and RUST_LOG
+    #[arg(long, global = true)]
+    pub quiet: bool,
+
+    /// Briefly show and hide the frame at startup and check that both took effect and that
+    /// the frame is drawn in the right color
+    #[arg(long)]
+    pub selftest_window: bool,
+
+    /// Run only the status polling (and the shared memory status section, if enabled),
+    /// without the frame window or tray icon
+    #[arg(long, conflicts_with_all = ["selftest_window", "simulate_streaming"])]
+    pub no_window: bool,
+
+    /// Show the live frame without any OAuth or YouTube API calls, to check how the overlay
+    /// looks with the current config
+    #[arg(long)]
+    pub simulate_streaming: bool,
+
+    /// With --simulate-streaming, hide the frame and exit after this many seconds
+    #[arg(long, value_name = "SECS", requires = "simulate_streaming")]
+    pub simulate_duration: Option<u64>,
+
+    /// Print the OAuth authorization URL and exit, without starting the callback server or
+    /// opening a browser. Finish with --complete-auth
+    #[arg(long, conflicts_with = "complete_auth")]
+    pub print_auth_url: bool,
+
+    /// Exchange the code (or the whole redirect URL) from a --print-auth-url authorization for
+    /// a token, then exit
+    #[arg(long, value_name = "CODE")]
+    pub complete_auth: Option<String>,
+
+    /// Print the total time streamed across sessions and exit
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Check the streaming status once, print it and exit. Uses the access token in
+    /// LIVEFRAME_ACCESS_TOKEN if set, without touching the stored token
+    #[arg(long)]
+    pub check_once: bool,
+
+    /// Like --check-once, but also show the live frame for --once-duration seconds if a stream
+    /// is live, then exit
+    #[arg(long, conflicts_with = "check_once")]
+    pub once: bool,
+
+    /// With --once, how long the frame is shown while live. 0 only prints the status
+    #[arg(long, value_name = "SECS", default_value_t = 5, requires = "once")]
+    pub once_duration: u64,
+
+    /// Print the broadcasts the YouTube API reports (active, upcoming and completed) with
+    /// their status, then exit. Like --check-once, uses LIVEFRAME_ACCESS_TOKEN if set
+    #[arg(long)]
+    pub list_broadcasts: bool,
+
+    /// Run the OAuth flow at startup even if a valid token is stored, e.g. to switch accounts
+    #[arg(long)]
+    pub force: bool,
+
+    /// Take the streaming status from this script instead of the YouTube API, without any
+    /// OAuth calls. Each line is a status (offline, pre_show, ready, encoder_connected, live or
+    /// error) and optionally the number of polls it lasts
+    #[cfg(feature = "mock-provider")]
+    #[arg(long, value_name = "FILE")]
+    pub mock_script: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the frame, checking the status when the channel's YouTube feed reports a change
+    /// (via PubSubHubbub, see [pubsub] in the config) instead of every few seconds
+    Watch,
+
+    #[command(flatten)]
+    OneShot(OneShotCommand),
+}
+
+// Subcommands that do their job and exit instead of running the frame
+#[derive(Subcommand)]
+pub enum OneShotCommand {
+    /// Collect system information for bug reports
+    Diagnose {
+        /// Write the report to this file instead of printing it
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Print the effective configuration as TOML, with secrets redacted
+    ExportConfig,
+
+    /// Show the overlay and save a PNG screenshot of the main display, e.g. for bug reports
+    Screenshot {
+        /// Directory (or file path) for the screenshot. Defaults to the current directory
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Leave the overlay on screen after the capture, until Ctrl+C
+        #[arg(long)]
+        keep_visible: bool,
+    },
+
+    /// Save the config, client secrets and stats (not the token) to a timestamped zip archive
+    BackupConfig {
+        /// Directory (or file path) for the archive. Defaults to the current directory
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Restore the files from a backup-config archive, keeping replaced files as *.bak
+    RestoreConfig {
+        /// The archive to restore
+        archive: PathBuf,
+    },
+
+    /// Run the OAuth flow, save the new token and exit, replacing any stored token
+    Auth,
+
+    /// Copy liveframe to %ProgramData%\liveframe and start it at logon (asks for
+    /// administrator rights)
+    Install {
+        #[command(flatten)]
+        target: InstallTarget,
+    },
+
+    /// Remove the logon task and the installed copy (asks for administrator rights)
+    Uninstall {
+        #[command(flatten)]
+        target: InstallTarget,
+    },
+}
+
+// The account liveframe is installed for. The unelevated process passes it on when it asks for
+// administrator rights, since the elevated process may run as another (administrator) account.
+#[derive(Args)]
+pub struct InstallTarget {
+    /// Account (DOMAIN\name) the logon task runs as
+    #[arg(long, hide = true)]
+    pub user: Option<String>,
+
+    /// The account's ~/.liveframe directory
+    #[arg(long, hide = true)]
+    pub liveframe_dir: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn watch_and_one_shot_commands_are_parsed() {
+        let cli = Cli::try_parse_from(["liveframe", "watch"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Watch)));
+
+        let cli = Cli::try_parse_from(["liveframe", "export-config"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::OneShot(OneShotCommand::ExportConfig))
+        ));
+    }
+}
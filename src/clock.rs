@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+
+// Source of the current time for time-dependent decisions (token expiry, stream start and end
+// timers), so they can be driven by a fake clock instead of waiting in real time
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+// The system clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+// A clock that only moves when advanced, for tests of time-dependent decisions
+#[cfg(test)]
+pub struct FakeClock {
+    now: std::sync::Mutex<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new() -> Self {
+        Self {
+            now: std::sync::Mutex::new("2024-05-01T12:00:00Z".parse().unwrap()),
+        }
+    }
+
+    pub fn advance(&self, by: std::time::Duration) {
+        *self.now.lock().unwrap() += chrono::Duration::from_std(by).unwrap();
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
@@ -0,0 +1,912 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Datelike, Local, NaiveTime, Weekday};
+use dirs::home_dir;
+use log::{debug, error, info};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::models::StatusKind;
+
+// How often the config file's modification time is checked
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_millis(250);
+// Editors often write a file several times when saving, so a change is only applied once the
+// file has been left alone for this long
+const CONFIG_RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
+// Application configuration, loaded from ~/.liveframe/config.toml.
+// Every field has a default so a missing file or a partial file is fine.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    // Most verbose level logged. RUST_LOG and --quiet override it.
+    pub log_level: LogLevel,
+    pub log_file: LogFileConfig,
+    pub youtube: YouTubeConfig,
+    pub oauth: OAuthConfig,
+    pub pubsub: PubSubConfig,
+    pub control: ControlConfig,
+    pub sound: SoundConfig,
+    pub frame: FrameConfig,
+    pub status: StatusConfig,
+    pub quiet_hours: QuietHoursConfig,
+    pub colors: FrameColors,
+    // Entries are checked in order; the first match overrides `colors`.
+    pub color_schedule: Vec<ColorScheduleEntry>,
+    // Additional frame windows, each in its own color and shown for some statuses only
+    pub regions: Vec<RegionConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    pub fn to_level_filter(self) -> log::LevelFilter {
+        match self {
+            LogLevel::Off => log::LevelFilter::Off,
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+        }
+    }
+}
+
+// Optional log file output. Console output is always kept.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct LogFileConfig {
+    pub enabled: bool,
+    // Defaults to ~/.liveframe/liveframe.log
+    pub path: Option<PathBuf>,
+    // The log file is rotated once it grows past this size.
+    pub max_size_kb: u64,
+    // Number of rotated files (liveframe.log.1, .2, ...) to keep.
+    pub max_files: u32,
+}
+
+impl Default for LogFileConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: None,
+            max_size_kb: 1024,
+            max_files: 5,
+        }
+    }
+}
+
+impl LogFileConfig {
+    // Resolves the log file path, falling back to ~/.liveframe/liveframe.log
+    pub fn resolved_path(&self) -> std::result::Result<PathBuf, Box<dyn std::error::Error>> {
+        match &self.path {
+            Some(path) => Ok(path.clone()),
+            None => Ok(get_liveframe_dir()?.join("liveframe.log")),
+        }
+    }
+}
+
+// YouTube detection settings
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct YouTubeConfig {
+    // When several broadcasts are live at once, only track the Nth (0-based) of them.
+    // Unset means any live broadcast counts.
+    pub broadcast_index: Option<usize>,
+    // Which live broadcasts make the stream count as live when there are several
+    pub multi_broadcast_mode: MultiBroadcastMode,
+    // Show the frame in the `ready` color while a broadcast is ready but not live yet
+    pub show_ready_indicator: bool,
+    // Show the frame in the `encoder_connected` color while an encoder is sending to one of the
+    // channel's ingest points but no broadcast is live yet. Costs an extra API call per poll.
+    pub show_encoder_indicator: bool,
+    // Only treat a stream as started once it has been reported live for this many seconds,
+    // to ignore broadcasts that flicker to live briefly. 0 shows the frame immediately.
+    pub stream_start_confirm_secs: u64,
+    // Keep treating a stream as live for this many seconds after it stops being reported live,
+    // so a brief encoder reconnect doesn't flicker the frame. 0 ends it immediately.
+    pub stream_end_grace_secs: u64,
+    // Show the frame in the `pre_show` color from this many minutes before an upcoming
+    // broadcast's scheduled start until it goes live. 0 disables it.
+    pub pre_show_mins: u64,
+    // Only broadcasts whose title matches are considered. Empty matches every broadcast.
+    pub broadcast_title_include_regex: String,
+    // Broadcasts whose title matches are ignored (e.g. an always-on maintenance broadcast).
+    // Empty ignores none.
+    pub broadcast_title_exclude_regex: String,
+    // API keys of additional Google Cloud projects, sent with each request so the quota is
+    // charged to them. The next key is used once one runs out of quota.
+    pub api_keys: Vec<String>,
+    // Poll every few seconds. When disabled, the status is only checked at startup and when
+    // "Refresh now" is picked from the tray menu.
+    pub continuous_polling: bool,
+    // Seconds between status checks while not live
+    pub poll_interval_offline_secs: u64,
+    // Seconds between status checks while live, to notice the end of the stream quickly
+    pub poll_interval_live_secs: u64,
+    // User-Agent sent with API requests. Empty uses liveframe/<version>.
+    pub user_agent: String,
+    // Second way to detect a live stream, consulted when the API lists no live broadcast
+    pub fallback_detection: FallbackDetection,
+    // ID of the channel (UC...) checked by the fallback detection
+    pub channel_id: String,
+    // Minimum seconds between loads of the live page. Polls in between reuse the last result.
+    pub live_page_interval_secs: u64,
+    // Show a notification the first time the live stream has more than this many concurrent
+    // viewers. Unset disables it (and the extra API call per poll).
+    pub viewer_alert_threshold: Option<u32>,
+    // After this many polls in a row have failed, show the frame in the `stale` color so broken
+    // detection isn't mistaken for being offline. 0 keeps the last status instead.
+    pub stale_after_errors: u32,
+    // How the frame shows that the status is stale
+    pub stale_indicator: StaleIndicator,
+    // Wait this many seconds after startup before the first poll, e.g. for a VPN to connect
+    // when started at login
+    pub startup_delay_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StaleIndicator {
+    // The frame stays on in the `stale` color
+    Steady,
+    // The frame blinks in the `stale` color
+    #[default]
+    Blink,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MultiBroadcastMode {
+    // Any live broadcast
+    #[default]
+    Any,
+    // Only once every active broadcast is live (e.g. a main and a backup stream). Broadcasts
+    // that haven't been started yet aren't active.
+    All,
+    // Only the broadcast with this ID
+    Specific {
+        id: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FallbackDetection {
+    #[default]
+    None,
+    // Load the channel's public /live page, which shows the live video while the channel has one
+    LivePage,
+}
+
+impl Default for YouTubeConfig {
+    fn default() -> Self {
+        Self {
+            broadcast_index: None,
+            multi_broadcast_mode: MultiBroadcastMode::default(),
+            show_ready_indicator: false,
+            show_encoder_indicator: false,
+            stream_start_confirm_secs: 0,
+            stream_end_grace_secs: 0,
+            pre_show_mins: 0,
+            broadcast_title_include_regex: String::new(),
+            broadcast_title_exclude_regex: String::new(),
+            api_keys: Vec::new(),
+            continuous_polling: true,
+            poll_interval_offline_secs: 5,
+            poll_interval_live_secs: 5,
+            user_agent: String::new(),
+            fallback_detection: FallbackDetection::None,
+            channel_id: String::new(),
+            live_page_interval_secs: 60,
+            viewer_alert_threshold: None,
+            stale_after_errors: 0,
+            stale_indicator: StaleIndicator::default(),
+            startup_delay_secs: 0,
+        }
+    }
+}
+
+// Compiled broadcast title filters from YouTubeConfig
+pub struct TitleFilter {
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+}
+
+impl TitleFilter {
+    pub fn matches(&self, title: &str) -> bool {
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.is_match(title))
+            && !self
+                .exclude
+                .as_ref()
+                .is_some_and(|exclude| exclude.is_match(title))
+    }
+}
+
+impl YouTubeConfig {
+    // Time between status checks, depending on whether the stream is live.
+    // At least a second, so a zero in the config doesn't poll in a busy loop.
+    pub fn poll_interval(&self, live: bool) -> Duration {
+        let secs = if live {
+            self.poll_interval_live_secs
+        } else {
+            self.poll_interval_offline_secs
+        };
+        Duration::from_secs(secs.max(1))
+    }
+
+    // Compiles the title regexes. Empty patterns are treated as unset.
+    pub fn title_filter(&self) -> std::result::Result<TitleFilter, Box<dyn std::error::Error>> {
+        let compile = |name: &str, pattern: &str| -> std::result::Result<_, String> {
+            if pattern.is_empty() {
+                return Ok(None);
+            }
+            Regex::new(pattern)
+                .map(Some)
+                .map_err(|e| format!("Invalid {}: {}", name, e))
+        };
+        Ok(TitleFilter {
+            include: compile(
+                "broadcast_title_include_regex",
+                &self.broadcast_title_include_regex,
+            )?,
+            exclude: compile(
+                "broadcast_title_exclude_regex",
+                &self.broadcast_title_exclude_regex,
+            )?,
+        })
+    }
+}
+
+// OAuth settings
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct OAuthConfig {
+    // Serve the callback on https://localhost with a generated self-signed certificate,
+    // for providers that reject plain http redirects. Google accepts http://localhost.
+    pub https_callback: bool,
+    // Where the OAuth token is stored
+    pub storage_backend: StorageBackend,
+    // Refresh the access token this many seconds before it expires, to tolerate clock skew
+    pub expiry_margin_secs: u64,
+    // Scopes requested during authorization. Scopes granted earlier are kept, so adding one
+    // only asks the user for the new permission.
+    pub scopes: Vec<String>,
+    // Redirect the browser here once authorization succeeds, instead of showing the success
+    // message. Empty shows the message.
+    pub success_redirect_url: String,
+    // Don't run the local callback server: the authorization uses the out-of-band redirect URI
+    // and the code Google shows is pasted into the console
+    pub out_of_band: bool,
+}
+
+impl Default for OAuthConfig {
+    fn default() -> Self {
+        Self {
+            https_callback: false,
+            storage_backend: StorageBackend::default(),
+            expiry_margin_secs: 60,
+            scopes: vec!["https://www.googleapis.com/auth/youtube.readonly".to_string()],
+            success_redirect_url: String::new(),
+            out_of_band: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackend {
+    // ~/.liveframe/token.json
+    #[default]
+    File,
+    // Windows Credential Manager, falling back to the file if it fails
+    CredentialManager,
+}
+
+// Push notification settings for `liveframe watch`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct PubSubConfig {
+    // ID of the YouTube channel (UC...) whose feed is subscribed to
+    pub channel_id: String,
+    // Public URL the hub delivers notifications to, forwarded to `listen_port` on this machine
+    pub callback_url: String,
+    // Local port the notification receiver listens on
+    pub listen_port: u16,
+    // Requested subscription lifetime. The subscription is renewed halfway through.
+    pub lease_secs: u64,
+    // Secret the hub signs notifications with, so forged ones are ignored. A random one is
+    // used for each run when empty.
+    pub secret: String,
+}
+
+impl Default for PubSubConfig {
+    fn default() -> Self {
+        Self {
+            channel_id: String::new(),
+            callback_url: String::new(),
+            listen_port: 8090,
+            lease_secs: 86400,
+            secret: String::new(),
+        }
+    }
+}
+
+// Sounds played when a stream starts or ends, for streamers not watching the screen
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SoundConfig {
+    pub enabled: bool,
+    // WAV files played when a stream goes live and when it ends. Unset plays nothing.
+    pub start_sound: Option<PathBuf>,
+    pub stop_sound: Option<PathBuf>,
+}
+
+// Local listener for commands forcing the frame shown or hidden, e.g. from a Stream Deck
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ControlConfig {
+    pub enabled: bool,
+    // Port on 127.0.0.1 the listener accepts /show, /hide and /auto on
+    pub listen_port: u16,
+    // Commands must carry this as `Authorization: Bearer <token>`. Empty accepts any local
+    // program.
+    pub token: String,
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_port: 8091,
+            token: String::new(),
+        }
+    }
+}
+
+// Sharing the streaming status with other programs
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct StatusConfig {
+    // Publish the status in the named shared memory section Local\liveframe_status
+    pub shared_memory: bool,
+}
+
+// Frame layout settings
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct FrameConfig {
+    // Full-screen frame or a small badge in one corner
+    pub style: FrameStyle,
+    // Screen edges the frame is drawn on
+    pub edges: Vec<FrameEdge>,
+    // Thickness of the frame in pixels
+    pub thickness: u32,
+    // Opacity of the frame, from 0 (invisible) to 100 (opaque)
+    pub opacity_percent: u8,
+    // Radius of rounded frame corners in pixels, 0 for square corners. Only applies to the full
+    // frame on all four edges.
+    pub border_radius: u32,
+    // Also render the frame into a named shared memory section for capture by other tools
+    pub mirror_shared_memory: bool,
+    // How the frame is drawn on screen
+    pub render_mode: RenderMode,
+    // Keep the frame hidden at startup until the first successful poll, for at most this many
+    // seconds after youtube.startup_delay_secs, so it doesn't flash in the idle color before a
+    // live stream is detected. 0 shows it right away.
+    pub startup_grace_secs: u64,
+    // Title of the frame window. It isn't shown anywhere on screen, but identifies the window in
+    // tools like Spy++ or AutoHotkey.
+    pub window_title: String,
+    // Fade between frame colors over this many milliseconds when the state changes. 0 switches
+    // instantly.
+    pub state_transition_ms: u64,
+    // Pixels the frame window is moved right/down and made wider/taller than the screen bounds,
+    // for displays whose reported coordinates are slightly off. Negative values move it
+    // left/up or shrink it.
+    pub x_offset: i32,
+    pub y_offset: i32,
+    pub width_adjust: i32,
+    pub height_adjust: i32,
+    // Screen areas the frame is never drawn over (e.g. a camera or timer overlay), which stay
+    // visible and click-through
+    pub exclude_rects: Vec<ExcludeRect>,
+    // Keep the frame within the work area, so it doesn't cover the taskbar
+    pub exclude_taskbar: bool,
+}
+
+impl Default for FrameConfig {
+    fn default() -> Self {
+        Self {
+            style: FrameStyle::Full,
+            edges: vec![
+                FrameEdge::Top,
+                FrameEdge::Bottom,
+                FrameEdge::Left,
+                FrameEdge::Right,
+            ],
+            thickness: 3,
+            opacity_percent: 100,
+            border_radius: 0,
+            mirror_shared_memory: false,
+            render_mode: RenderMode::Fill,
+            startup_grace_secs: 10,
+            window_title: "liveframe overlay".to_string(),
+            state_transition_ms: 500,
+            x_offset: 0,
+            y_offset: 0,
+            width_adjust: 0,
+            height_adjust: 0,
+            exclude_rects: Vec::new(),
+            exclude_taskbar: false,
+        }
+    }
+}
+
+// A rectangle in screen coordinates, with the top-left corner of the main display at (0, 0)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExcludeRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderMode {
+    // Solid rectangles along the configured edges
+    #[default]
+    Fill,
+    // The window border drawn by DWM in the frame color, with rounded anti-aliased corners,
+    // filled inside to the thickness. Needs Windows 11; older versions fall back to `Fill`.
+    DwmBorder,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameStyle {
+    // A frame around the whole screen, along the configured edges
+    Full,
+    // L-shaped brackets in the four corners of the screen, with arms `length` pixels long
+    CornerBrackets { length: u32 },
+    // A filled square of `size` pixels in one corner of the screen
+    CornerBadge { corner: Corner, size: u32 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameEdge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+// An additional frame window, independent of the main frame. Takes the same layout settings
+// as [frame]; the shared memory mirror and startup grace period only apply to the main frame.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RegionConfig {
+    #[serde(flatten)]
+    pub frame: FrameConfig,
+    pub color: Color,
+    // Statuses the region is shown for. Empty never shows it.
+    pub show_when: Vec<StatusKind>,
+}
+
+impl Default for RegionConfig {
+    fn default() -> Self {
+        Self {
+            frame: FrameConfig::default(),
+            color: Color::new(0xFF, 0x00, 0x00),
+            show_when: Vec::new(),
+        }
+    }
+}
+
+// An RGB color, written as "#RRGGBB" in the config file
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    // Returns the color as a Win32 COLORREF value (0x00BBGGRR)
+    pub fn to_colorref(self) -> u32 {
+        (self.b as u32) << 16 | (self.g as u32) << 8 | self.r as u32
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        let hex = value.strip_prefix('#').unwrap_or(&value);
+        if hex.len() != 6 {
+            return Err(format!("invalid color {:?}, expected \"#RRGGBB\"", value));
+        }
+        let rgb = u32::from_str_radix(hex, 16)
+            .map_err(|_| format!("invalid color {:?}, expected \"#RRGGBB\"", value))?;
+        Ok(Self::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+    }
+}
+
+// Frame colors for each state
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct FrameColors {
+    // No active stream
+    pub idle: Color,
+    // Live, stream audio is silent
+    pub silent: Color,
+    // Live, stream audio is audible
+    pub audible: Color,
+    // Broadcast ready but not live yet (with `show_ready_indicator`)
+    pub ready: Color,
+    // Encoder connected but not live yet (with `show_encoder_indicator`)
+    pub encoder_connected: Color,
+    // Broadcast scheduled to start within `pre_show_mins`
+    pub pre_show: Color,
+    // Detection failing (with `stale_after_errors`)
+    pub stale: Color,
+}
+
+impl Default for FrameColors {
+    fn default() -> Self {
+        Self {
+            idle: Color::new(0xFF, 0xFF, 0xFF),
+            silent: Color::new(0xFF, 0x00, 0x00),
+            audible: Color::new(0x00, 0xFF, 0x00),
+            ready: Color::new(0x80, 0x80, 0x00),
+            encoder_connected: Color::new(0xFF, 0x80, 0x00),
+            pre_show: Color::new(0x00, 0x80, 0xFF),
+            stale: Color::new(0xFF, 0xC0, 0x00),
+        }
+    }
+}
+
+// Daily time range during which the frame is never shown, even while live
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct QuietHoursConfig {
+    pub enabled: bool,
+    // The range may cross midnight (e.g. 22:00 - 07:00)
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl Default for QuietHoursConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: NaiveTime::from_hms_opt(22, 0, 0).unwrap_or_default(),
+            end: NaiveTime::from_hms_opt(7, 0, 0).unwrap_or_default(),
+        }
+    }
+}
+
+impl QuietHoursConfig {
+    pub fn is_active(&self, now: DateTime<Local>) -> bool {
+        self.enabled && time_in_range(now.time(), self.start, self.end)
+    }
+}
+
+// Overrides some of the frame colors on the given days and/or time range.
+// An empty `days` list matches every day; a missing start or end matches all day.
+// The time range may cross midnight (e.g. 22:00 - 02:00), and `days` refers to the
+// current local day.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ColorScheduleEntry {
+    pub days: Vec<Weekday>,
+    pub start: Option<NaiveTime>,
+    pub end: Option<NaiveTime>,
+    pub idle: Option<Color>,
+    pub silent: Option<Color>,
+    pub audible: Option<Color>,
+    pub ready: Option<Color>,
+    pub encoder_connected: Option<Color>,
+    pub pre_show: Option<Color>,
+    pub stale: Option<Color>,
+}
+
+impl ColorScheduleEntry {
+    pub fn matches(&self, now: DateTime<Local>) -> bool {
+        if !self.days.is_empty() && !self.days.contains(&now.weekday()) {
+            return false;
+        }
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => time_in_range(now.time(), start, end),
+            _ => true,
+        }
+    }
+}
+
+// Returns whether `time` is in [start, end), handling ranges that cross midnight
+pub fn time_in_range(time: NaiveTime, start: NaiveTime, end: NaiveTime) -> bool {
+    if start <= end {
+        start <= time && time < end
+    } else {
+        time >= start || time < end
+    }
+}
+
+impl Config {
+    // Returns the frame colors for the given time, applying the first matching schedule entry
+    pub fn frame_colors_at(&self, now: DateTime<Local>) -> FrameColors {
+        let mut colors = self.colors;
+        if let Some(entry) = self.color_schedule.iter().find(|entry| entry.matches(now)) {
+            colors.idle = entry.idle.unwrap_or(colors.idle);
+            colors.silent = entry.silent.unwrap_or(colors.silent);
+            colors.audible = entry.audible.unwrap_or(colors.audible);
+            colors.ready = entry.ready.unwrap_or(colors.ready);
+            colors.encoder_connected = entry.encoder_connected.unwrap_or(colors.encoder_connected);
+            colors.pre_show = entry.pre_show.unwrap_or(colors.pre_show);
+            colors.stale = entry.stale.unwrap_or(colors.stale);
+        }
+        colors
+    }
+}
+
+// Problems with the files in ~/.liveframe that the user has to fix
+#[derive(Debug)]
+pub enum ConfigError {
+    // secret.json holds web application credentials without a localhost redirect URI, so the
+    // OAuth flow can't complete
+    WebCredentialsNotSupported,
+    // secret.json holds neither desktop app nor web application credentials
+    MissingCredentials,
+    // [oauth] scopes is empty, so authorization would grant no access
+    NoOAuthScopes,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::WebCredentialsNotSupported => write!(
+                f,
+                "secret.json contains web application credentials, which liveframe can't use \
+                 without a localhost redirect URI. In the Google Cloud Console, create an OAuth \
+                 client ID of type \"Desktop app\" (APIs & Services > Credentials) and download \
+                 its JSON as ~/.liveframe/secret.json"
+            ),
+            ConfigError::MissingCredentials => write!(
+                f,
+                "secret.json has neither \"installed\" nor \"web\" credentials"
+            ),
+            ConfigError::NoOAuthScopes => write!(
+                f,
+                "[oauth] scopes is empty, list at least one scope (e.g. \
+                 \"https://www.googleapis.com/auth/youtube.readonly\")"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// Function to get the ~/.liveframe directory
+pub fn get_liveframe_dir() -> std::result::Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut path = home_dir().ok_or("Could not find home directory")?;
+    path.push(".liveframe");
+    Ok(path)
+}
+
+// Writes a file so that it holds either its old or its new contents even if the process dies
+// midway: the contents go to a temporary file next to it, which then replaces it
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+
+    let result = File::create(&temp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    // On the same volume, the rename replaces the file in one step
+    let result = result.and_then(|()| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+// Function to get the path to the config file
+pub fn get_config_path() -> std::result::Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_liveframe_dir()?.join("config.toml"))
+}
+
+// Function to load the config file, using defaults if it doesn't exist
+pub fn load_config(config_path: &Path) -> std::result::Result<Config, Box<dyn std::error::Error>> {
+    if !config_path.exists() {
+        return Ok(Config::default());
+    }
+
+    let contents = fs::read_to_string(config_path)?;
+    let config: Config = toml::from_str(&contents)
+        .map_err(|e| format!("Failed to parse {}: {}", config_path.display(), e))?;
+    config.youtube.title_filter()?;
+    if config
+        .oauth
+        .scopes
+        .iter()
+        .all(|scope| scope.trim().is_empty())
+    {
+        return Err(ConfigError::NoOAuthScopes.into());
+    }
+    Ok(config)
+}
+
+// Watches the config file and publishes the reloaded config once a burst of writes settles.
+// A config that fails to load is logged and skipped, keeping the previous one.
+pub fn watch_config(config_path: PathBuf, initial: Config) -> watch::Receiver<Config> {
+    let (tx, rx) = watch::channel(initial);
+
+    tokio::spawn(async move {
+        let modified_time = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        let mut applied: Option<SystemTime> = modified_time(&config_path);
+        let mut pending: Option<(Option<SystemTime>, Instant)> = None;
+
+        loop {
+            tokio::time::sleep(CONFIG_POLL_INTERVAL).await;
+
+            let current = modified_time(&config_path);
+            match pending {
+                Some((seen, _)) if seen != current => pending = Some((current, Instant::now())),
+                None if current != applied => {
+                    debug!("Config file changed, waiting for writes to settle...");
+                    pending = Some((current, Instant::now()));
+                }
+                _ => {}
+            }
+
+            let Some((seen, since)) = pending else {
+                continue;
+            };
+            if since.elapsed() < CONFIG_RELOAD_DEBOUNCE {
+                continue;
+            }
+            pending = None;
+            applied = seen;
+
+            match load_config(&config_path) {
+                Ok(config) => {
+                    info!("Config reloaded from {}", config_path.display());
+                    if tx.send(config).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => error!("Failed to reload config, keeping the previous one: {}", e),
+            }
+        }
+    });
+
+    rx
+}
+
+// Keys whose values must never be printed
+const SECRET_KEYS: &[&str] = &[
+    "client_secret",
+    "access_token",
+    "refresh_token",
+    "api_keys",
+    "secret",
+];
+
+// Returns raw config file contents with the values of secret keys replaced by a placeholder,
+// wherever and however they're written (multi-line arrays, inline tables). Contents that don't
+// parse as TOML are redacted as a whole, since the secrets can't be located in them.
+pub fn redact_secrets(contents: &str) -> String {
+    let Ok(mut value) = contents.parse::<toml::Value>() else {
+        return "<redacted: not valid TOML>".to_string();
+    };
+    redact_value(&mut value);
+    toml::to_string_pretty(&value)
+        .unwrap_or_else(|_| "<redacted: could not be serialized>".to_string())
+}
+
+// Serializes the effective config as TOML, with secret values redacted
+pub fn export_config(config: &Config) -> std::result::Result<String, Box<dyn std::error::Error>> {
+    let mut value = toml::Value::try_from(config)?;
+    redact_value(&mut value);
+    Ok(toml::to_string_pretty(&value)?)
+}
+
+fn redact_value(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                if SECRET_KEYS.contains(&key.as_str()) {
+                    *value = toml::Value::String("<redacted>".to_string());
+                } else {
+                    redact_value(value);
+                }
+            }
+        }
+        toml::Value::Array(array) => array.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_redacted_in_any_layout() {
+        let contents = r#"
+oauth = { client_secret = "GOCSPX-inline" }
+
+[youtube]
+api_keys = [
+    "AIzaSyFirst",
+    "AIzaSySecond",
+]
+poll_interval_secs = 30
+
+[pubsub]
+"secret" = "hub-secret"
+"#;
+
+        let redacted = redact_secrets(contents);
+        for secret in ["GOCSPX-inline", "AIzaSyFirst", "AIzaSySecond", "hub-secret"] {
+            assert!(
+                !redacted.contains(secret),
+                "{} leaked:\n{}",
+                secret,
+                redacted
+            );
+        }
+        assert!(redacted.contains("poll_interval_secs = 30"));
+    }
+
+    #[test]
+    fn invalid_config_is_redacted_as_a_whole() {
+        let redacted = redact_secrets("api_keys = [\"AIzaSyUnterminated\"\n[youtube");
+        assert!(!redacted.contains("AIzaSy"));
+    }
+}
@@ -0,0 +1,210 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+use hyper::header::{AUTHORIZATION, HOST, ORIGIN};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::{error, info, warn};
+use tokio::sync::Notify;
+
+use crate::config::ControlConfig;
+
+// Visibility of the frame as set by control commands, independently of the streaming status
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameOverride {
+    // Follow the streaming status and quiet hours
+    Auto,
+    // Always show the frame, even during quiet hours
+    Show,
+    // Always hide the frame
+    Hide,
+}
+
+impl FrameOverride {
+    fn name(self) -> &'static str {
+        match self {
+            FrameOverride::Auto => "auto",
+            FrameOverride::Show => "show",
+            FrameOverride::Hide => "hide",
+        }
+    }
+}
+
+static OVERRIDE: AtomicU8 = AtomicU8::new(FrameOverride::Auto as u8);
+
+// Notified when a control command changes the override, so the main loop applies it right away
+pub static OVERRIDE_CHANGED: Notify = Notify::const_new();
+
+// Returns the current override
+pub fn frame_override() -> FrameOverride {
+    match OVERRIDE.load(Ordering::SeqCst) {
+        x if x == FrameOverride::Show as u8 => FrameOverride::Show,
+        x if x == FrameOverride::Hide as u8 => FrameOverride::Hide,
+        _ => FrameOverride::Auto,
+    }
+}
+
+// Starts the control listener on localhost. A `POST` to /show, /hide or /auto sets the override,
+// with the configured token if any; any accepted request answers with the current one.
+pub fn start(config: &ControlConfig) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    // Only local programs may control the frame
+    let addr = SocketAddr::from(([127, 0, 0, 1], config.listen_port));
+    let token = Arc::new(config.token.clone());
+    let make_service = make_service_fn(move |_| {
+        let token = token.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req| {
+                let token = token.clone();
+                async move { handle_request(req, &token) }
+            }))
+        }
+    });
+    let server = Server::try_bind(&addr)?.serve(make_service);
+    info!("Listening for control commands on {}", addr);
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            error!("Control listener stopped: {}", e);
+        }
+    });
+    Ok(())
+}
+
+fn handle_request(
+    req: Request<Body>,
+    token: &str,
+) -> std::result::Result<Response<Body>, hyper::Error> {
+    let mut response = Response::new(Body::empty());
+    if let Some(status) = rejection(&req, token) {
+        *response.status_mut() = status;
+        return Ok(response);
+    }
+
+    let requested = match req.uri().path() {
+        "/show" => Some(FrameOverride::Show),
+        "/hide" => Some(FrameOverride::Hide),
+        "/auto" => Some(FrameOverride::Auto),
+        "/" => None,
+        _ => {
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            return Ok(response);
+        }
+    };
+    if let Some(requested) = requested {
+        if req.method() != Method::POST {
+            *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+            return Ok(response);
+        }
+        let previous = OVERRIDE.swap(requested as u8, Ordering::SeqCst);
+        if previous != requested as u8 {
+            info!("Control command: {}", requested.name());
+            OVERRIDE_CHANGED.notify_one();
+        }
+    }
+    *response.body_mut() = Body::from(format!("{}\n", frame_override().name()));
+    Ok(response)
+}
+
+// Returns the status to reject a request with, if it isn't from a local program or lacks the
+// token. Web pages can reach the listener through the browser, so requests carrying an Origin
+// header or a Host other than this machine (DNS rebinding) are refused.
+fn rejection(req: &Request<Body>, token: &str) -> Option<StatusCode> {
+    if !matches!(*req.method(), Method::GET | Method::POST) {
+        return Some(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    let headers = req.headers();
+    if headers.contains_key(ORIGIN) {
+        warn!("Rejected a control request from a web page");
+        return Some(StatusCode::FORBIDDEN);
+    }
+    if let Some(host) = headers.get(HOST) {
+        if !host.to_str().is_ok_and(is_loopback_host) {
+            warn!("Rejected a control request for host {:?}", host);
+            return Some(StatusCode::FORBIDDEN);
+        }
+    }
+    if !token.is_empty() {
+        let expected = format!("Bearer {}", token);
+        if headers
+            .get(AUTHORIZATION)
+            .is_none_or(|value| value != expected.as_str())
+        {
+            warn!("Rejected a control request without the configured token");
+            return Some(StatusCode::UNAUTHORIZED);
+        }
+    }
+    None
+}
+
+// Whether a Host header names this machine, with or without a port
+fn is_loopback_host(host: &str) -> bool {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if !name.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    };
+    matches!(name, "127.0.0.1" | "localhost" | "[::1]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: Method, headers: &[(&str, &str)]) -> Request<Body> {
+        let mut builder = Request::builder().method(method).uri("/hide");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn local_requests_are_accepted() {
+        let req = request(Method::POST, &[("host", "127.0.0.1:8091")]);
+        assert_eq!(rejection(&req, ""), None);
+        let req = request(Method::POST, &[("host", "localhost")]);
+        assert_eq!(rejection(&req, ""), None);
+    }
+
+    #[test]
+    fn browser_requests_are_rejected() {
+        let req = request(
+            Method::POST,
+            &[
+                ("host", "127.0.0.1:8091"),
+                ("origin", "https://example.com"),
+            ],
+        );
+        assert_eq!(rejection(&req, ""), Some(StatusCode::FORBIDDEN));
+        let req = request(Method::POST, &[("host", "attacker.example:8091")]);
+        assert_eq!(rejection(&req, ""), Some(StatusCode::FORBIDDEN));
+    }
+
+    #[test]
+    fn configured_token_is_required() {
+        let req = request(Method::POST, &[("host", "127.0.0.1:8091")]);
+        assert_eq!(rejection(&req, "s3cret"), Some(StatusCode::UNAUTHORIZED));
+        let req = request(
+            Method::POST,
+            &[
+                ("host", "127.0.0.1:8091"),
+                ("authorization", "Bearer wrong"),
+            ],
+        );
+        assert_eq!(rejection(&req, "s3cret"), Some(StatusCode::UNAUTHORIZED));
+        let req = request(
+            Method::POST,
+            &[
+                ("host", "127.0.0.1:8091"),
+                ("authorization", "Bearer s3cret"),
+            ],
+        );
+        assert_eq!(rejection(&req, "s3cret"), None);
+    }
+
+    #[test]
+    fn commands_require_post() {
+        let response = handle_request(request(Method::GET, &[]), "").unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(frame_override(), FrameOverride::Auto);
+    }
+}
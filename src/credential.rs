@@ -0,0 +1,74 @@
+use log::warn;
+use windows::core::*;
+use windows::Win32::Foundation::{GetLastError, ERROR_NOT_FOUND};
+use windows::Win32::Security::Credentials::{
+    CredDeleteW, CredFree, CredReadW, CredWriteW, CREDENTIALW, CRED_PERSIST_LOCAL_MACHINE,
+    CRED_TYPE_GENERIC,
+};
+
+use crate::models::TokenInfo;
+
+// Target name of the generic credential holding the token, as shown in Credential Manager
+const CREDENTIAL_TARGET: PCWSTR = w!("liveframe/youtube");
+
+// Stores the token in Windows Credential Manager as a JSON blob
+pub fn write_token(token_info: &TokenInfo) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let mut blob = serde_json::to_vec(token_info)?;
+    let credential = CREDENTIALW {
+        Type: CRED_TYPE_GENERIC,
+        TargetName: PWSTR(CREDENTIAL_TARGET.as_ptr() as *mut u16),
+        CredentialBlobSize: blob.len() as u32,
+        CredentialBlob: blob.as_mut_ptr(),
+        Persist: CRED_PERSIST_LOCAL_MACHINE,
+        ..Default::default()
+    };
+
+    unsafe {
+        if !CredWriteW(&credential, 0).as_bool() {
+            return Err(format!("CredWriteW failed: {}", Error::from_win32()).into());
+        }
+    }
+    Ok(())
+}
+
+// Reads the token from Windows Credential Manager. Returns None if no token is stored.
+// A corrupted credential is deleted, like a corrupted token file.
+pub fn read_token() -> std::result::Result<Option<TokenInfo>, Box<dyn std::error::Error>> {
+    unsafe {
+        let mut credential: *mut CREDENTIALW = std::ptr::null_mut();
+        if !CredReadW(CREDENTIAL_TARGET, CRED_TYPE_GENERIC.0, 0, &mut credential).as_bool() {
+            if GetLastError() == ERROR_NOT_FOUND {
+                return Ok(None);
+            }
+            return Err(format!("CredReadW failed: {}", Error::from_win32()).into());
+        }
+
+        let blob = std::slice::from_raw_parts(
+            (*credential).CredentialBlob,
+            (*credential).CredentialBlobSize as usize,
+        );
+        let token_info = serde_json::from_slice(blob);
+        CredFree(credential as *const _);
+
+        match token_info {
+            Ok(token_info) => Ok(Some(token_info)),
+            Err(e) => {
+                warn!("Stored credential is corrupted ({}), deleting it", e);
+                delete_token()?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+// Removes the stored token, if any
+pub fn delete_token() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    unsafe {
+        if !CredDeleteW(CREDENTIAL_TARGET, CRED_TYPE_GENERIC.0, 0).as_bool()
+            && GetLastError() != ERROR_NOT_FOUND
+        {
+            return Err(format!("CredDeleteW failed: {}", Error::from_win32()).into());
+        }
+    }
+    Ok(())
+}
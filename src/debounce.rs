@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+
+use crate::clock::Clock;
+use crate::models::StreamStatus;
+
+// Holds back streaming status changes around going live: a newly detected stream until it has
+// stayed live for the start confirmation window, and an ended stream until the end grace period
+// has passed in case the encoder reconnects
+#[derive(Default)]
+pub struct StatusDebouncer {
+    // When the stream was first reported live, while polls keep reporting it live
+    live_since: Option<DateTime<Utc>>,
+    // When the live stream was first reported as ended, while waiting out the grace period
+    hide_scheduled_at: Option<DateTime<Utc>>,
+    // When the stream that apply last ended was first reported as ended
+    ended_at: Option<DateTime<Utc>>,
+}
+
+impl StatusDebouncer {
+    // Returns the status to switch to from `current`, given the status a poll reported
+    pub fn apply(
+        &mut self,
+        current: &StreamStatus,
+        mut new_status: StreamStatus,
+        confirm: Duration,
+        grace: Duration,
+        clock: &dyn Clock,
+    ) -> StreamStatus {
+        if matches!(new_status, StreamStatus::Live(_)) {
+            let now = clock.now();
+            let live_for = elapsed_since(*self.live_since.get_or_insert(now), now);
+            if !matches!(current, StreamStatus::Live(_)) && live_for < confirm {
+                debug!(
+                    "Stream reported live for {}s, waiting for {}s before showing it",
+                    live_for.as_secs(),
+                    confirm.as_secs()
+                );
+                new_status = current.clone();
+            }
+        } else {
+            self.live_since = None;
+        }
+
+        if matches!(new_status, StreamStatus::Live(_)) {
+            if self.hide_scheduled_at.take().is_some() {
+                info!("Stream resumed within the end grace period");
+            }
+        } else if matches!(current, StreamStatus::Live(_)) {
+            let scheduled_at = *self.hide_scheduled_at.get_or_insert_with(|| {
+                if !grace.is_zero() {
+                    info!(
+                        "Stream no longer reported live, waiting {}s before ending it",
+                        grace.as_secs()
+                    );
+                }
+                clock.now()
+            });
+            if elapsed_since(scheduled_at, clock.now()) < grace {
+                new_status = current.clone();
+            } else {
+                self.hide_scheduled_at = None;
+                self.ended_at = Some(scheduled_at);
+            }
+        }
+        new_status
+    }
+
+    // Returns when the stream apply last ended was first reported as ended, which is when it
+    // actually ended rather than when the grace period ran out
+    pub fn take_ended_at(&mut self) -> Option<DateTime<Utc>> {
+        self.ended_at.take()
+    }
+}
+
+// Time from `since` to `now`, zero if the clock went backwards
+fn elapsed_since(since: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+    (now - since).to_std().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::models::LiveStream;
+
+    const CONFIRM: Duration = Duration::from_secs(30);
+    const GRACE: Duration = Duration::from_secs(60);
+
+    fn live() -> StreamStatus {
+        StreamStatus::Live(LiveStream {
+            video_id: "video".to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn live_stream_is_held_back_until_confirmed() {
+        let clock = FakeClock::new();
+        let mut debouncer = StatusDebouncer::default();
+        let offline = StreamStatus::Offline;
+
+        assert_eq!(
+            debouncer.apply(&offline, live(), CONFIRM, GRACE, &clock),
+            offline
+        );
+        clock.advance(Duration::from_secs(29));
+        assert_eq!(
+            debouncer.apply(&offline, live(), CONFIRM, GRACE, &clock),
+            offline
+        );
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            debouncer.apply(&offline, live(), CONFIRM, GRACE, &clock),
+            live()
+        );
+    }
+
+    #[test]
+    fn confirmation_restarts_after_a_blip() {
+        let clock = FakeClock::new();
+        let mut debouncer = StatusDebouncer::default();
+        let offline = StreamStatus::Offline;
+
+        debouncer.apply(&offline, live(), CONFIRM, GRACE, &clock);
+        clock.advance(Duration::from_secs(20));
+        debouncer.apply(&offline, StreamStatus::Offline, CONFIRM, GRACE, &clock);
+        clock.advance(Duration::from_secs(20));
+        assert_eq!(
+            debouncer.apply(&offline, live(), CONFIRM, GRACE, &clock),
+            offline
+        );
+    }
+
+    #[test]
+    fn ended_stream_stays_live_for_the_grace_period() {
+        let clock = FakeClock::new();
+        let mut debouncer = StatusDebouncer::default();
+
+        assert_eq!(
+            debouncer.apply(&live(), StreamStatus::Offline, CONFIRM, GRACE, &clock),
+            live()
+        );
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(
+            debouncer.apply(&live(), StreamStatus::Offline, CONFIRM, GRACE, &clock),
+            live()
+        );
+        assert_eq!(debouncer.take_ended_at(), None);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            debouncer.apply(&live(), StreamStatus::Offline, CONFIRM, GRACE, &clock),
+            StreamStatus::Offline
+        );
+        // The stream ended at the first poll reporting it ended
+        assert_eq!(
+            debouncer.take_ended_at(),
+            Some(clock.now() - chrono::Duration::seconds(60))
+        );
+        assert_eq!(debouncer.take_ended_at(), None);
+    }
+
+    #[test]
+    fn stream_resumed_within_the_grace_period_stays_live() {
+        let clock = FakeClock::new();
+        let mut debouncer = StatusDebouncer::default();
+
+        debouncer.apply(&live(), StreamStatus::Offline, CONFIRM, GRACE, &clock);
+        clock.advance(Duration::from_secs(45));
+        assert_eq!(
+            debouncer.apply(&live(), live(), CONFIRM, GRACE, &clock),
+            live()
+        );
+        // The grace period starts over on the next end
+        clock.advance(Duration::from_secs(45));
+        assert_eq!(
+            debouncer.apply(&live(), StreamStatus::Offline, CONFIRM, GRACE, &clock),
+            live()
+        );
+    }
+
+    #[test]
+    fn zero_grace_ends_the_stream_right_away() {
+        let clock = FakeClock::new();
+        let mut debouncer = StatusDebouncer::default();
+
+        assert_eq!(
+            debouncer.apply(
+                &live(),
+                StreamStatus::Offline,
+                CONFIRM,
+                Duration::ZERO,
+                &clock
+            ),
+            StreamStatus::Offline
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use chrono::Utc;
+use log::info;
+use windows::core::PWSTR;
+use windows::Win32::System::SystemInformation::OSVERSIONINFOW;
+use windows::Win32::System::WindowsProgramming::GetUserNameW;
+
+use crate::cli;
+use crate::config::{self, Config, StorageBackend, YouTubeConfig};
+use crate::credential;
+use crate::logging;
+use crate::models::TokenInfo;
+use crate::oauth;
+use crate::window;
+use crate::youtube;
+
+// Number of log lines included in the report
+const LOG_TAIL_LINES: usize = 50;
+
+// RtlGetVersion reports the real OS version, unlike GetVersionExW which is subject to
+// compatibility shims. It is not part of the windows crate bindings.
+#[link(name = "ntdll")]
+extern "system" {
+    fn RtlGetVersion(version_information: *mut OSVERSIONINFOW) -> i32;
+}
+
+// Collects system debug information and prints it, or writes it to `output`
+pub async fn run_diagnose(
+    config: &Config,
+    config_path: Option<&Path>,
+    output: Option<&Path>,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let report = build_report(config, config_path).await;
+
+    match output {
+        Some(path) => {
+            fs::write(path, &report)?;
+            info!("Diagnostic report written to {}", path.display());
+        }
+        None => println!("{}", report),
+    }
+
+    Ok(())
+}
+
+// Builds the report as a Markdown code block suitable for pasting into a GitHub issue
+async fn build_report(config: &Config, config_path: Option<&Path>) -> String {
+    let mut report = String::new();
+    report.push_str("```text\n");
+
+    let _ = writeln!(report, "liveframe version: {}", cli::LONG_VERSION);
+    let _ = writeln!(report, "OS version: {}", os_version());
+    let _ = writeln!(report, "Current user: {}", current_user());
+
+    // Config file
+    report.push('\n');
+    match config_path {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(contents) => {
+                let _ = writeln!(report, "Config file ({}):", path.display());
+                let _ = writeln!(report, "{}", config::redact_secrets(&contents));
+            }
+            Err(_) => {
+                let _ = writeln!(report, "Config file ({}): not found", path.display());
+            }
+        },
+        None => {
+            let _ = writeln!(report, "Config file: could not be located");
+        }
+    }
+
+    // Stored token
+    report.push('\n');
+    let token = load_token(config);
+    match &token {
+        Ok(Some(token)) => {
+            let state = if Utc::now() < token.expiry {
+                "valid"
+            } else {
+                "expired"
+            };
+            let _ = writeln!(
+                report,
+                "Token ({:?}): present, expiry {} ({})",
+                config.oauth.storage_backend, token.expiry, state
+            );
+        }
+        Ok(None) => {
+            let _ = writeln!(report, "Token: not found");
+        }
+        Err(e) => {
+            let _ = writeln!(report, "Token: unreadable ({})", e);
+        }
+    }
+
+    // YouTube API reachability
+    let access_token = match &token {
+        Ok(Some(token)) => Some(token.access_token.as_str()),
+        _ => None,
+    };
+    let _ = writeln!(
+        report,
+        "YouTube API: {}",
+        check_api_reachability(&config.youtube, access_token).await
+    );
+
+    // Window creation
+    let window_result = unsafe { window::test_window_creation() };
+    let _ = writeln!(
+        report,
+        "Window creation: {}",
+        match window_result {
+            Ok(()) => "ok".to_string(),
+            Err(e) => format!("failed ({})", e),
+        }
+    );
+
+    // Log file tail
+    report.push('\n');
+    match config.log_file.resolved_path() {
+        Ok(path) => match logging::tail_log_file(&path, LOG_TAIL_LINES) {
+            Ok(lines) => {
+                let _ = writeln!(
+                    report,
+                    "Last {} log lines ({}):",
+                    lines.len(),
+                    path.display()
+                );
+                for line in lines {
+                    let _ = writeln!(report, "{}", line);
+                }
+            }
+            Err(_) => {
+                let _ = writeln!(report, "Log file ({}): not found", path.display());
+            }
+        },
+        Err(e) => {
+            let _ = writeln!(report, "Log file: {}", e);
+        }
+    }
+
+    report.push_str("```");
+    report
+}
+
+// Returns the real OS version, or the NTSTATUS RtlGetVersion failed with
+pub fn real_os_version() -> std::result::Result<OSVERSIONINFOW, i32> {
+    let mut info = OSVERSIONINFOW {
+        dwOSVersionInfoSize: std::mem::size_of::<OSVERSIONINFOW>() as u32,
+        ..Default::default()
+    };
+    let status = unsafe { RtlGetVersion(&mut info) };
+    if status != 0 {
+        return Err(status);
+    }
+    Ok(info)
+}
+
+fn os_version() -> String {
+    let info = match real_os_version() {
+        Ok(info) => info,
+        Err(status) => return format!("unknown (RtlGetVersion failed: {:#x})", status),
+    };
+    format!(
+        "Windows {}.{} (build {})",
+        info.dwMajorVersion, info.dwMinorVersion, info.dwBuildNumber
+    )
+}
+
+fn current_user() -> String {
+    let mut buffer = [0u16; 257];
+    let mut size = buffer.len() as u32;
+    let ok = unsafe { GetUserNameW(PWSTR(buffer.as_mut_ptr()), &mut size) };
+    if !ok.as_bool() || size == 0 {
+        return "unknown".to_string();
+    }
+    // size includes the terminating null
+    String::from_utf16_lossy(&buffer[..size as usize - 1])
+}
+
+fn load_token(
+    config: &Config,
+) -> std::result::Result<Option<TokenInfo>, Box<dyn std::error::Error>> {
+    if config.oauth.storage_backend == StorageBackend::CredentialManager {
+        if let Some(token_info) = credential::read_token()? {
+            return Ok(Some(token_info));
+        }
+    }
+
+    let token_path = oauth::get_token_path()?;
+    if !token_path.exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(token_path)?;
+    Ok(Some(serde_json::from_str(&contents)?))
+}
+
+// Makes a single API call. Any HTTP response (even 401 without a token) proves reachability.
+// The configured User-Agent is sent, as proxies may filter on it.
+async fn check_api_reachability(config: &YouTubeConfig, access_token: Option<&str>) -> String {
+    let client = match youtube::api_client(config) {
+        Ok(client) => client,
+        Err(e) => return format!("unchecked ({})", e),
+    };
+    let mut request = client
+        .get("https://www.googleapis.com/youtube/v3/liveBroadcasts")
+        .query(&[("part", "id"), ("broadcastStatus", "active")]);
+    if let Some(access_token) = access_token {
+        request = request.header("Authorization", format!("Bearer {}", access_token));
+    }
+
+    let started = Instant::now();
+    match request.send().await {
+        Ok(response) => format!(
+            "reachable, HTTP {} in {}ms{}",
+            response.status(),
+            started.elapsed().as_millis(),
+            if access_token.is_none() {
+                " (no token, unauthenticated call)"
+            } else {
+                ""
+            }
+        ),
+        Err(e) => format!("unreachable ({})", e),
+    }
+}
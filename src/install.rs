@@ -0,0 +1,295 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use windows::core::{w, PCWSTR};
+use windows::Win32::UI::Shell::{IsUserAnAdmin, ShellExecuteW};
+use windows::Win32::UI::WindowsAndMessaging::SW_SHOW;
+
+use crate::cli::InstallTarget;
+use crate::config;
+
+// Name of the Task Scheduler task that starts liveframe at logon
+const TASK_NAME: &str = "liveframe";
+
+// Attempts to remove the install directory while the stopped copy exits, and the time between
+const REMOVE_ATTEMPTS: u32 = 10;
+const REMOVE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+// Function to get the %ProgramData%\liveframe install directory
+fn get_install_dir() -> PathBuf {
+    let program_data = std::env::var_os("ProgramData").unwrap_or_else(|| r"C:\ProgramData".into());
+    PathBuf::from(program_data).join("liveframe")
+}
+
+// Copies the executable to %ProgramData%\liveframe, creates the config directory and registers
+// a logon task that starts it. Relaunches itself elevated if needed.
+pub fn run_install(target: &InstallTarget) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    if !is_elevated() {
+        return relaunch_elevated();
+    }
+
+    let install_dir = get_install_dir();
+    fs::create_dir_all(&install_dir)?;
+    let exe_path = install_dir.join("liveframe.exe");
+    let current_exe = std::env::current_exe()?;
+    if current_exe != exe_path {
+        // A running copy keeps its executable from being replaced
+        if exe_path.exists() {
+            stop_installed_copy(&exe_path);
+        }
+        fs::copy(&current_exe, &exe_path)
+            .map_err(|e| format!("Failed to copy {}: {}", exe_path.display(), e))?;
+    }
+    info!("Installed {}", exe_path.display());
+
+    let config_dir = target_liveframe_dir(target)?;
+    fs::create_dir_all(&config_dir)?;
+    info!("Config directory: {}", config_dir.display());
+
+    let task_run = format!("\"{}\"", exe_path.display());
+    let mut args = vec![
+        "/Create", "/F", "/TN", TASK_NAME, "/SC", "ONLOGON", "/RL", "LIMITED", "/TR", &task_run,
+    ];
+    // Without /RU the task would run as the account that created it, which is the administrator
+    // approving the UAC prompt rather than the user. /IT needs no password for it.
+    let user = target.user.clone().or_else(current_user);
+    if let Some(user) = &user {
+        args.extend(["/RU", user, "/IT"]);
+    }
+    run_schtasks(&args)?;
+    info!(
+        "Registered the \"{}\" logon task for {}",
+        TASK_NAME,
+        user.as_deref().unwrap_or("the current user")
+    );
+
+    if !config_dir.join("secret.json").exists() {
+        warn!(
+            "Copy your OAuth client secrets to {} before the next logon",
+            config_dir.join("secret.json").display()
+        );
+    }
+    Ok(())
+}
+
+// Removes the logon task, the installed executable and the config directory (if empty).
+// Relaunches itself elevated if needed.
+pub fn run_uninstall(
+    target: &InstallTarget,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    if !is_elevated() {
+        return relaunch_elevated();
+    }
+
+    let install_dir = get_install_dir();
+    if std::env::current_exe()?.starts_with(&install_dir) {
+        return Err(format!(
+            "Can't remove the running executable, run uninstall from a copy outside {}",
+            install_dir.display()
+        )
+        .into());
+    }
+
+    // The running copy keeps its executable from being removed. The task is ended before it's
+    // deleted.
+    stop_installed_copy(&install_dir.join("liveframe.exe"));
+    match run_schtasks(&["/Delete", "/F", "/TN", TASK_NAME]) {
+        Ok(()) => info!("Removed the \"{}\" logon task", TASK_NAME),
+        Err(e) => warn!("{}", e),
+    }
+
+    if install_dir.exists() {
+        remove_dir_retrying(&install_dir)
+            .map_err(|e| format!("Failed to remove {}: {}", install_dir.display(), e))?;
+        info!("Removed {}", install_dir.display());
+    }
+
+    // The token, secrets and config are kept unless the directory was never used
+    let config_dir = target_liveframe_dir(target)?;
+    match fs::remove_dir(&config_dir) {
+        Ok(()) => info!("Removed {}", config_dir.display()),
+        Err(_) if config_dir.exists() => info!(
+            "Kept {}, which still contains files; delete it to remove your settings and token",
+            config_dir.display()
+        ),
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+fn is_elevated() -> bool {
+    unsafe { IsUserAnAdmin().as_bool() }
+}
+
+// Runs this executable again with the same arguments through the UAC prompt, adding the
+// settings of this process the elevated one wouldn't see: LIVEFRAME_CONFIG (the elevated
+// process gets a fresh environment), and the account and ~/.liveframe directory to install for
+fn relaunch_elevated() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    info!("Administrator rights are needed, requesting elevation...");
+    let exe = std::env::current_exe()?;
+    let mut args: Vec<OsString> = std::env::args_os().skip(1).collect();
+    let has_config = args.iter().any(|arg| {
+        let arg = arg.to_string_lossy();
+        arg == "--config" || arg.starts_with("--config=")
+    });
+    if let (false, Some(config)) = (has_config, std::env::var_os("LIVEFRAME_CONFIG")) {
+        args.extend(["--config".into(), config]);
+    }
+    if let Some(user) = current_user() {
+        args.extend(["--user".into(), user.into()]);
+    }
+    args.extend([
+        "--liveframe-dir".into(),
+        config::get_liveframe_dir()?.into_os_string(),
+    ]);
+    let args = args
+        .iter()
+        .map(|arg| quote_arg(&arg.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let exe_wide: Vec<u16> = exe
+        .as_os_str()
+        .to_string_lossy()
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    let args_wide: Vec<u16> = args.encode_utf16().chain(std::iter::once(0)).collect();
+
+    let result = unsafe {
+        ShellExecuteW(
+            None,
+            w!("runas"),
+            PCWSTR::from_raw(exe_wide.as_ptr()),
+            PCWSTR::from_raw(args_wide.as_ptr()),
+            PCWSTR::null(),
+            SW_SHOW,
+        )
+    };
+    if result.0 <= 32 {
+        error!("Failed to start elevated, error code: {}", result.0);
+        return Err(format!("Elevation was refused or failed, error code: {}", result.0).into());
+    }
+
+    info!("Continuing in the elevated window");
+    Ok(())
+}
+
+// The ~/.liveframe directory of the account liveframe is installed for
+fn target_liveframe_dir(
+    target: &InstallTarget,
+) -> std::result::Result<PathBuf, Box<dyn std::error::Error>> {
+    match &target.liveframe_dir {
+        Some(dir) => Ok(dir.clone()),
+        None => config::get_liveframe_dir(),
+    }
+}
+
+// The account this process runs as, as DOMAIN\name
+fn current_user() -> Option<String> {
+    let domain = std::env::var("USERDOMAIN").ok()?;
+    let name = std::env::var("USERNAME").ok()?;
+    Some(format!("{}\\{}", domain, name))
+}
+
+// Ends the logon task and any other process running the installed executable
+fn stop_installed_copy(exe_path: &Path) {
+    match run_schtasks(&["/End", "/TN", TASK_NAME]) {
+        Ok(()) => info!("Stopped the \"{}\" logon task", TASK_NAME),
+        Err(e) => debug!("{}", e),
+    }
+    // Also a copy started by hand, matched by path so other copies keep running
+    let script = format!(
+        "Get-Process liveframe -ErrorAction SilentlyContinue | \
+         Where-Object Path -eq '{}' | Stop-Process -Force",
+        exe_path.display().to_string().replace('\'', "''")
+    );
+    let stopped = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .output();
+    if let Err(e) = stopped {
+        warn!("Failed to stop the running copy of liveframe: {}", e);
+    }
+}
+
+// Removes the directory, retrying while a stopped process still holds files in it
+fn remove_dir_retrying(dir: &Path) -> std::io::Result<()> {
+    let mut attempt = 1;
+    loop {
+        match fs::remove_dir_all(dir) {
+            Err(e) if attempt < REMOVE_ATTEMPTS && dir.exists() => {
+                debug!("Failed to remove {} ({}), retrying", dir.display(), e);
+                std::thread::sleep(REMOVE_RETRY_DELAY);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+// Quotes an argument for a Windows command line, as the C runtime splits it
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => {
+                backslashes += 1;
+                continue;
+            }
+            // Backslashes before a quote are escaped, as is the quote
+            '"' => quoted.push_str(&"\\".repeat(backslashes * 2 + 1)),
+            _ => quoted.push_str(&"\\".repeat(backslashes)),
+        }
+        quoted.push(c);
+        backslashes = 0;
+    }
+    // Backslashes before the closing quote are escaped too
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+fn run_schtasks(args: &[&str]) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let output = Command::new("schtasks").args(args).output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "schtasks {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_argument_is_not_quoted() {
+        assert_eq!(quote_arg("install"), "install");
+        assert_eq!(
+            quote_arg(r"C:\Users\me\config.toml"),
+            r"C:\Users\me\config.toml"
+        );
+    }
+
+    #[test]
+    fn argument_with_spaces_or_quotes_is_quoted() {
+        assert_eq!(quote_arg(""), r#""""#);
+        assert_eq!(
+            quote_arg(r"C:\Users\Jane Doe\.liveframe\"),
+            r#""C:\Users\Jane Doe\.liveframe\\""#
+        );
+        assert_eq!(quote_arg(r#"say "hi""#), r#""say \"hi\"""#);
+    }
+}
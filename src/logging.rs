@@ -0,0 +1,157 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use env_logger::{Builder, Target};
+
+use crate::config::{LogFileConfig, LogLevel};
+
+// Log file writer that rotates liveframe.log -> liveframe.log.1 -> ... once the file
+// grows past max_size, keeping at most max_files rotated files.
+pub struct RotatingFileWriter {
+    path: PathBuf,
+    max_size: u64,
+    max_files: u32,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFileWriter {
+    pub fn new(path: PathBuf, max_size: u64, max_files: u32) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_size,
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    fn rotated_path(&self, index: u32) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        if self.max_files > 0 {
+            // Drop the oldest file, then shift the others up by one.
+            let oldest = self.rotated_path(self.max_files);
+            if oldest.exists() {
+                fs::remove_file(&oldest)?;
+            }
+            for i in (1..self.max_files).rev() {
+                let from = self.rotated_path(i);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(i + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+
+        // Truncate covers max_files == 0, where the current file is simply discarded.
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+// Writes every log record to stderr and to the log file.
+// Failures writing the file are ignored so console logging keeps working.
+struct TeeWriter {
+    file: RotatingFileWriter,
+}
+
+impl Write for TeeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::stderr().write_all(buf)?;
+        let _ = self.file.write_all(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let _ = self.file.flush();
+        io::stderr().flush()
+    }
+}
+
+// Initialize the logger with timestamps, optionally teeing to a rotating log file.
+// Logs up to the configured level; RUST_LOG directives (e.g. liveframe=debug) take precedence,
+// and `quiet` limits logging to at most warnings and errors regardless of both.
+pub fn init_logger(log_file: &LogFileConfig, level: LogLevel, quiet: bool) {
+    let mut builder = Builder::new();
+    builder
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "{} [{}] {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                record.level(),
+                record.args()
+            )
+        })
+        .filter(None, level.to_level_filter());
+    if quiet {
+        builder.filter(None, level.to_level_filter().min(log::LevelFilter::Warn));
+    } else {
+        builder.parse_env("RUST_LOG");
+    }
+
+    let mut file_error = None;
+    if log_file.enabled {
+        match open_log_file(log_file) {
+            Ok(file) => {
+                builder.target(Target::Pipe(Box::new(TeeWriter { file })));
+            }
+            Err(e) => file_error = Some(e),
+        }
+    }
+
+    builder.init();
+
+    if let Some(e) = file_error {
+        log::error!("Failed to open log file, logging to console only: {}", e);
+    }
+}
+
+fn open_log_file(
+    log_file: &LogFileConfig,
+) -> std::result::Result<RotatingFileWriter, Box<dyn std::error::Error>> {
+    let path = log_file.resolved_path()?;
+    let writer = RotatingFileWriter::new(path, log_file.max_size_kb * 1024, log_file.max_files)?;
+    Ok(writer)
+}
+
+// Returns the last `lines` lines of the given log file
+pub fn tail_log_file(path: &Path, lines: usize) -> io::Result<Vec<String>> {
+    let contents = fs::read_to_string(path)?;
+    let all: Vec<&str> = contents.lines().collect();
+    let start = all.len().saturating_sub(lines);
+    Ok(all[start..].iter().map(|s| s.to_string()).collect())
+}
@@ -1,73 +1,275 @@
 mod audio;
+mod backup;
+mod cli;
+mod clock;
+mod config;
+mod control;
+mod credential;
+mod debounce;
+mod diagnose;
+mod install;
+mod logging;
+mod mirror;
+#[cfg(any(test, feature = "mock-provider"))]
+mod mock;
 mod models;
 mod oauth;
+mod poll_timing;
+mod pubsub;
+mod quota;
+mod renderer;
+mod retry;
+mod screenshot;
+mod sound;
+mod stats;
+mod transition;
+mod tray;
 mod window;
 mod youtube;
 
-use std::sync::mpsc;
-use std::thread;
-use std::time::Duration;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use chrono::Utc;
-use env_logger::Builder;
-use log::{debug, error, info};
+use chrono::{DateTime, Local, Utc};
+use clap::Parser;
+use log::{debug, error, info, warn};
+use tokio::sync::watch;
 use tokio_util::sync::CancellationToken;
+use windows::Win32::Foundation::COLORREF;
 
-use crate::audio::SendHwnd;
+use crate::cli::{Cli, Command, OneShotCommand};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{
+    Config, FrameConfig, FrameEdge, FrameStyle, QuietHoursConfig, RenderMode, StaleIndicator,
+};
+use crate::control::FrameOverride;
+use crate::debounce::StatusDebouncer;
+use crate::mirror::StatusMirror;
+use crate::models::{AppState, LastError, LiveStream, StatusKind, StreamStatus, TokenInfo};
+use crate::poll_timing::PollTimings;
+use crate::pubsub::PubSub;
+use crate::quota::QuotaKeyPool;
+use crate::transition::FrameAction;
+use crate::window::{WindowCommand, WindowHandle, WindowRole, WindowThread};
+
+// Time between streaming status checks in `liveframe watch` while feed notifications are
+// delivered, as a safety net for missed notifications
+const PUSH_POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+// Minimum time between the starts of status checks triggered by feed notifications, which can
+// come in bursts
+const MIN_NOTIFIED_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+// Upper bound of the exponential backoff after rate-limited polls without a Retry-After header
+const MAX_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+// Time the frame is on and off while blinking to show a stale status
+const STALE_BLINK_INTERVAL: Duration = Duration::from_millis(500);
 
 #[tokio::main]
 async fn main() -> windows::core::Result<()> {
-    // Initialize the logger with timestamps
-    Builder::new()
-        .format(|buf, record| {
-            use std::io::Write;
-            writeln!(
-                buf,
-                "{} [{}] {}",
-                chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
-                record.level(),
-                record.args()
-            )
-        })
-        .filter(None, log::LevelFilter::Info)
-        .init();
+    let cli = Cli::parse();
+
+    // Load the config before the logger so the log file settings can be applied
+    let config_path = match cli.config.clone() {
+        Some(path) => Ok(path),
+        None => config::get_config_path(),
+    };
+    let config_result = config_path
+        .as_ref()
+        .map_err(|e| e.to_string().into())
+        .and_then(|path| config::load_config(path));
+    let mut config = config_result.as_ref().cloned().unwrap_or_default();
 
-    info!("liveframe v{} starting...", env!("CARGO_PKG_VERSION"));
+    logging::init_logger(&config.log_file, config.log_level, cli.quiet);
 
-    // Create a channel for sending the window handle from the window thread to the main thread
-    let (tx, rx) = mpsc::channel();
+    info!("liveframe v{} starting...", cli::LONG_VERSION);
 
-    // Spawn a thread to create the window and run the message loop
-    let _window_thread =
-        thread::spawn(move || unsafe { window::create_window_and_run_message_loop(tx) });
+    retry::RETRY_BUDGET.spawn_refill();
 
-    // Wait to receive the window handle from the window thread
-    let hwnd = match rx.recv() {
-        Ok(handle) => handle,
-        Err(e) => {
-            error!("Failed to receive window handle: {}", e);
+    if let Err(e) = &config_result {
+        error!("Failed to load config: {}", e);
+        // diagnose still runs with the default config so a broken config can be reported
+        if !matches!(
+            cli.command,
+            Some(Command::OneShot(OneShotCommand::Diagnose { .. }))
+        ) {
             return Err(windows::core::Error::from_win32());
         }
+    }
+
+    // `watch` runs the frame like no subcommand, only polling differently
+    let watch = matches!(cli.command, Some(Command::Watch));
+    match cli.command {
+        None | Some(Command::Watch) => {}
+        Some(Command::OneShot(command)) => {
+            return run_command(command, &config, config_path.ok().as_deref()).await
+        }
+    }
+
+    if cli.stats {
+        return exit_on_error(stats::print_stats());
+    }
+    if cli.check_once {
+        return exit_on_error(check_once(&config, cli.force).await.map(|_| ()));
+    }
+    if cli.once {
+        return exit_on_error(check_once_and_show(&config, cli.force, cli.once_duration).await);
+    }
+    if cli.list_broadcasts {
+        return exit_on_error(list_broadcasts(&config, cli.force).await);
+    }
+
+    #[cfg(feature = "mock-provider")]
+    let mut mock_provider = match &cli.mock_script {
+        Some(path) => match mock::MockStreamProvider::from_file(path) {
+            Ok(provider) => Some(provider),
+            Err(e) => {
+                error!("Failed to load the mock script: {}", e);
+                return Err(windows::core::Error::from_win32());
+            }
+        },
+        None => None,
+    };
+    #[cfg(feature = "mock-provider")]
+    let mocked = mock_provider.is_some();
+    #[cfg(not(feature = "mock-provider"))]
+    let mocked = false;
+
+    // Fail before the window appears if the OAuth credentials are missing
+    // (the simulation and the mock script don't use them)
+    if cli.simulate_streaming {
+        info!("Simulating a live stream, no YouTube calls will be made");
+    } else if mocked {
+        info!("Using the mock status script, no YouTube calls will be made");
+    } else if let Err(e) = oauth::validate_client_secrets() {
+        error!("{}", e);
+        return Err(windows::core::Error::from_win32());
+    }
+
+    if cli.print_auth_url {
+        return exit_on_error(oauth::print_auth_url(&config.oauth));
+    }
+    if let Some(code) = &cli.complete_auth {
+        return exit_on_error(oauth::complete_auth(&config.oauth, code).await.map(|_| ()));
+    }
+
+    let (window, window_thread) = if cli.no_window {
+        info!("Running without the frame window");
+        (WindowHandle::detached(), None)
+    } else {
+        let (window, window_thread) =
+            window::spawn_frame_window(config.frame.clone(), WindowRole::Main)?;
+        (window, Some(window_thread))
     };
 
-    // Show a white frame until an active stream is detected.
-    unsafe {
-        if hwnd.0 != 0 {
-            window::set_color_state(hwnd, window::COLOR_WHITE);
-            window::set_window_visibility(hwnd, true);
-            debug!("Window initially shown as idle");
+    // The additional regions start hidden and follow the streaming status. They aren't closed
+    // explicitly; their windows go away with the process.
+    let mut regions = Vec::new();
+    if !cli.no_window {
+        for region in &config.regions {
+            let role = WindowRole::Region(COLORREF(region.color.to_colorref()));
+            match window::spawn_frame_window(region.frame.clone(), role) {
+                Ok((handle, _)) => regions.push(Region {
+                    show_when: region.show_when.clone(),
+                    window: handle,
+                    visible: false,
+                }),
+                Err(e) => error!("Failed to create a frame region: {}", e),
+            }
         }
     }
 
-    // Setup Ctrl+C handler for graceful exit
+    // Show the idle frame until an active stream is detected. Within the startup grace period
+    // it stays hidden until the first poll tells whether a stream is live.
+    window::set_palette(&window, &config.frame_colors_at(Local::now()));
+    window::set_color_state(&window, window::COLOR_IDLE);
+
+    if cli.selftest_window {
+        selftest_window(&window, &config.frame).await;
+    }
+    // The grace period counts from the end of the startup delay, which comes before the first
+    // poll
+    let startup_grace = Duration::from_secs(config.frame.startup_grace_secs);
+    let mut startup_show = if startup_grace.is_zero() {
+        show_frame(&window, frame_hidden(&config.quiet_hours));
+        debug!("Window initially shown as idle");
+        None
+    } else {
+        let window = window.clone();
+        let quiet_hours = config.quiet_hours.clone();
+        let startup_delay = Duration::from_secs(config.youtube.startup_delay_secs);
+        Some(tokio::spawn(async move {
+            tokio::time::sleep(startup_delay + startup_grace).await;
+            info!("No successful poll within the startup grace period, showing the idle frame");
+            show_frame(&window, frame_hidden(&quiet_hours));
+        }))
+    };
+
+    // Setup Ctrl+C handler for graceful exit. Closing the window first removes the tray icon.
+    // During authorization the first Ctrl+C cancels the OAuth flow instead, so its callback
+    // server shuts down gracefully and main exits once the flow returns.
+    let window_thread = Arc::new(Mutex::new(window_thread));
+    let exit_window = window.clone();
+    let exit_window_thread = window_thread.clone();
+    let oauth_cancel = CancellationToken::new();
+    let authorizing = Arc::new(AtomicBool::new(false));
+    let handler_oauth_cancel = oauth_cancel.clone();
+    let handler_authorizing = authorizing.clone();
     ctrlc::set_handler(move || {
+        if handler_authorizing.load(Ordering::SeqCst) && !handler_oauth_cancel.is_cancelled() {
+            info!("Received Ctrl+C, cancelling authorization...");
+            handler_oauth_cancel.cancel();
+            return;
+        }
         info!("Received Ctrl+C, exiting normally...");
+        stats::end_session(Utc::now());
+        close_window(&exit_window, &exit_window_thread);
         std::process::exit(0);
     })
     .expect("Error setting Ctrl+C handler");
 
+    if cli.simulate_streaming {
+        simulate_streaming(&window, cli.simulate_duration).await;
+        close_window(&window, &window_thread);
+        return Ok(());
+    }
+
+    if config.control.enabled {
+        if let Err(e) = control::start(&config.control) {
+            error!("Failed to start the control listener: {}", e);
+        }
+    }
+
+    // Give the network time to come up when started at login. The token refresh needs it too.
+    let startup_delay = config.youtube.startup_delay_secs;
+    if startup_delay > 0 {
+        info!("Waiting {} seconds before first poll", startup_delay);
+        tokio::time::sleep(Duration::from_secs(startup_delay)).await;
+    }
+
     // Get OAuth token (either from file or through auth flow)
-    let token_info = match oauth::get_oauth_token().await {
+    authorizing.store(true, Ordering::SeqCst);
+    let token_result = if mocked {
+        // A token that never needs refreshing
+        Ok(TokenInfo {
+            access_token: String::new(),
+            refresh_token: String::new(),
+            expiry: DateTime::<Utc>::MAX_UTC,
+            scopes: Vec::new(),
+        })
+    } else {
+        oauth::get_oauth_token(&config.oauth, cli.force, &oauth_cancel).await
+    };
+    authorizing.store(false, Ordering::SeqCst);
+    if oauth_cancel.is_cancelled() {
+        info!("Authorization cancelled, exiting...");
+        close_window(&window, &window_thread);
+        return Ok(());
+    }
+    let token_info = match token_result {
         Ok(token) => token,
         Err(e) => {
             error!("Failed to get OAuth token: {}", e);
@@ -75,31 +277,167 @@ async fn main() -> windows::core::Result<()> {
         }
     };
 
+    // Subscribe to the channel feed, so a notification triggers the next check
+    let pubsub = if watch {
+        match PubSub::start(&config.pubsub) {
+            Ok(pubsub) => Some(pubsub),
+            Err(e) => {
+                error!("Failed to start watching the channel feed: {}", e);
+                close_window(&window, &window_thread);
+                return Err(windows::core::Error::from_win32());
+            }
+        }
+    } else {
+        None
+    };
+
+    // Pick up config edits while running. Settings used at startup ([log_file], [oauth] and
+    // [frame] other than thickness) still need a restart; colors, thickness and [youtube] apply
+    // right away or from the next poll.
+    let mut config_rx = match &config_path {
+        Ok(path) => Some(config::watch_config(path.clone(), config.clone())),
+        Err(_) => None,
+    };
+
     // Main loop to check YouTube streaming status
-    let mut current_video_id: Option<String> = None;
+    let mut current_status = StreamStatus::Offline;
     let mut audio_task: Option<(CancellationToken, tokio::task::JoinHandle<()>)> = None;
     let mut token = token_info;
-    let send_hwnd = SendHwnd(hwnd);
+    let mut app_state = AppState::default();
+    let clock = SystemClock;
+    let mut debouncer = StatusDebouncer::default();
+    let mut key_pool = QuotaKeyPool::new(config.youtube.api_keys.clone());
+    let mut in_quiet_hours = false;
+    // The control command override last applied to the frame
+    let mut applied_override = FrameOverride::Auto;
+    // Whether the API already reported missing scopes and the user was asked to authorize again
+    let mut reauthorized_for_scopes = false;
+    // Whether sound cues play for status changes. The first successful poll is the baseline, so
+    // a stream already live then (e.g. after a restart) doesn't play the start cue.
+    let mut sound_cues_armed = false;
+    // Whether the viewer alert was shown for the current live stream
+    let mut viewer_alert_shown = false;
+    let mut poll_timings = PollTimings::new();
+    // Blinks the frame while the status is stale, with `stale_indicator = "blink"`
+    let mut stale_blink: Option<tokio::task::JoinHandle<()>> = None;
+    let mut status_mirror = if config.status.shared_memory {
+        match unsafe { StatusMirror::create() } {
+            Ok(mirror) => Some(mirror),
+            Err(e) => {
+                error!("Failed to create the shared memory status section: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
 
     loop {
+        // Quiet hours hide the frame whatever the streaming status. Control commands override
+        // both.
+        let frame_override = control::frame_override();
+        let quiet_hours_active = config.quiet_hours.is_active(Local::now());
+        if quiet_hours_active != in_quiet_hours {
+            in_quiet_hours = quiet_hours_active;
+            if frame_override != FrameOverride::Auto {
+                info!(
+                    "Quiet hours {}, keeping the frame as set by the control command",
+                    if quiet_hours_active {
+                        "started"
+                    } else {
+                        "ended"
+                    }
+                );
+            } else if quiet_hours_active {
+                info!("Quiet hours started, hiding the frame");
+                window.send(WindowCommand::Hide);
+            } else if startup_show.is_none() {
+                info!("Quiet hours ended, showing the frame");
+                window.send(WindowCommand::Show);
+            }
+        }
+        if frame_override != applied_override {
+            applied_override = frame_override;
+            apply_frame_override(
+                &window,
+                frame_override,
+                quiet_hours_active,
+                &mut startup_show,
+            );
+        }
+        let quiet = frame_hidden(&config.quiet_hours);
+
+        let poll_started = Instant::now();
+        // Minimum delay before the next poll, set when the API rate limits us
+        let mut rate_limit_backoff: Option<Duration> = None;
+
         // Check if token needs refresh
-        let current_time = Utc::now();
-        if current_time >= token.expiry {
+        if oauth::token_needs_refresh(&config.oauth, &token, &clock) {
             info!("Token expired, refreshing...");
-            match oauth::refresh_token(&token.refresh_token).await {
+            match oauth::refresh_token(&config.oauth, &token.refresh_token).await {
                 Ok(new_token) => token = new_token,
-                Err(e) => error!("Failed to refresh token: {}", e),
+                Err(e) => {
+                    error!("Failed to refresh token: {}", e);
+                    if e.is::<retry::RetryBudgetExhausted>() {
+                        app_state.last_error = Some(LastError {
+                            message: e.to_string(),
+                            at: Local::now(),
+                        });
+                    }
+                }
             }
         }
 
+        // Re-evaluate the color schedule so a stream crossing into another schedule entry updates
+        window::set_palette(&window, &config.frame_colors_at(Local::now()));
+
         // Check YouTube streaming status
         debug!("Check streaming status...");
-        match youtube::check_youtube_streaming(&token.access_token).await {
-            Ok(new_video_id) => {
-                if new_video_id != current_video_id {
+        #[cfg(feature = "mock-provider")]
+        let status_result = match &mut mock_provider {
+            Some(provider) => provider.next_status(),
+            None => {
+                youtube::check_youtube_streaming(
+                    &token.access_token,
+                    &config.youtube,
+                    &mut key_pool,
+                )
+                .await
+            }
+        };
+        #[cfg(not(feature = "mock-provider"))]
+        let status_result =
+            youtube::check_youtube_streaming(&token.access_token, &config.youtube, &mut key_pool)
+                .await;
+        match status_result {
+            Ok(new_status) => {
+                app_state.last_error = None;
+                app_state.consecutive_errors = 0;
+                if !matches!(new_status, StreamStatus::Live(_)) {
+                    sound_cues_armed = true;
+                }
+                if window::set_stale(&window, false) {
+                    info!("Streaming status check succeeded again, the status is no longer stale");
+                    if let Some(stale_blink) = stale_blink.take() {
+                        stale_blink.abort();
+                        show_frame(&window, quiet);
+                    }
+                }
+
+                // Hold back a newly detected stream until it has stayed live long enough, and
+                // keep an ended stream live for the grace period in case the encoder reconnects
+                let new_status = debouncer.apply(
+                    &current_status,
+                    new_status,
+                    Duration::from_secs(config.youtube.stream_start_confirm_secs),
+                    Duration::from_secs(config.youtube.stream_end_grace_secs),
+                    &clock,
+                );
+
+                if new_status != current_status {
                     info!(
                         "Streaming state changed: {:?} -> {:?}",
-                        current_video_id, new_video_id
+                        current_status, new_status
                     );
 
                     // Stop any existing audio task.
@@ -107,42 +445,613 @@ async fn main() -> windows::core::Result<()> {
                         cancel.cancel();
                         let _ = handle.await;
                     }
-                    // Reset color state for the next session.
-                    window::set_color_state(hwnd, window::COLOR_UNKNOWN);
 
-                    match &new_video_id {
-                        Some(id) => {
-                            unsafe {
-                                window::set_window_visibility(hwnd, true);
+                    // Add the finished session to the lifetime stats. The API reports when the
+                    // broadcast went live, but no longer lists it once it has ended.
+                    // A change of the live broadcast's details (e.g. its title) keeps the session.
+                    match (&current_status, &new_status) {
+                        (StreamStatus::Live(old), StreamStatus::Live(new))
+                            if old.video_id == new.video_id => {}
+                        _ => {
+                            // The stream ended when it was first reported ended, not when the
+                            // grace period ran out
+                            stats::end_session(
+                                debouncer.take_ended_at().unwrap_or_else(|| clock.now()),
+                            );
+                            if let StreamStatus::Live(stream) = &new_status {
+                                stats::start_session(
+                                    &stream.video_id,
+                                    stream.started_at.unwrap_or_else(|| clock.now()),
+                                );
                             }
-                            let cancel = CancellationToken::new();
-                            let cancel_task = cancel.clone();
-                            let id_clone = id.clone();
-                            let hwnd_clone = send_hwnd;
-                            let handle = tokio::spawn(async move {
-                                if let Err(e) =
-                                    audio::run_audio_task(id_clone, hwnd_clone, cancel_task).await
-                                {
-                                    error!("audio task failed: {:#}", e);
-                                }
-                            });
-                            audio_task = Some((cancel, handle));
                         }
-                        None => {
-                            window::set_color_state(hwnd, window::COLOR_WHITE);
-                            unsafe {
-                                window::set_window_visibility(hwnd, true);
+                    }
+
+                    match (&current_status, &new_status) {
+                        (StreamStatus::Live(_), StreamStatus::Live(_)) => {}
+                        (_, StreamStatus::Live(_)) if !sound_cues_armed => {
+                            debug!("Stream was already live at startup, skipping the start cue");
+                            sound_cues_armed = true;
+                        }
+                        (_, StreamStatus::Live(_)) => {
+                            sound::play_cue(&config.sound, sound::Cue::StreamStarted)
+                        }
+                        (StreamStatus::Live(_), _) => {
+                            sound::play_cue(&config.sound, sound::Cue::StreamEnded)
+                        }
+                        _ => {}
+                    }
+
+                    if let StreamStatus::Live(stream) = &new_status {
+                        info!(
+                            "Live: https://www.youtube.com/watch?v={} (thumbnail: {})",
+                            stream.video_id,
+                            stream.thumbnail_url.as_deref().unwrap_or("none")
+                        );
+                        if quiet {
+                            info!("Not showing the live frame during quiet hours");
+                        }
+                    }
+                    for action in transition::status_change_actions(&new_status, quiet) {
+                        match action {
+                            FrameAction::SetColorState(state) => {
+                                window::set_color_state(&window, state)
                             }
+                            FrameAction::Show => window.send(WindowCommand::Show),
                         }
                     }
+                    if let StreamStatus::Live(stream) = &new_status {
+                        let cancel = CancellationToken::new();
+                        let cancel_task = cancel.clone();
+                        let id_clone = stream.video_id.clone();
+                        let window_clone = window.clone();
+                        let handle = tokio::spawn(async move {
+                            if let Err(e) =
+                                audio::run_audio_task(id_clone, window_clone, cancel_task).await
+                            {
+                                error!("audio task failed: {:#}", e);
+                            }
+                        });
+                        audio_task = Some((cancel, handle));
+                    }
+
+                    current_status = new_status;
+                }
+
+                if let Some(status_mirror) = status_mirror.as_mut() {
+                    let (streaming, title) = match &current_status {
+                        StreamStatus::Live(stream) => (true, stream.title.as_str()),
+                        _ => (false, ""),
+                    };
+                    status_mirror.write(streaming, title, clock.now().timestamp());
+                }
+
+                // The first successful poll ends the startup grace period
+                if let Some(startup_show) = startup_show.take() {
+                    startup_show.abort();
+                    show_frame(&window, quiet);
+                }
+            }
+            // Only once per run, so a scope missing from the config doesn't reopen the browser
+            // on every poll
+            Err(e) if e.is::<youtube::InsufficientScopeError>() && !reauthorized_for_scopes => {
+                reauthorized_for_scopes = true;
+                warn!("{}", e);
+                info!("Authorizing again to grant the configured scopes...");
+                authorizing.store(true, Ordering::SeqCst);
+                let token_result = oauth::get_oauth_token(&config.oauth, true, &oauth_cancel).await;
+                authorizing.store(false, Ordering::SeqCst);
+                if oauth_cancel.is_cancelled() {
+                    info!("Authorization cancelled, exiting...");
+                    close_window(&window, &window_thread);
+                    return Ok(());
+                }
+                match token_result {
+                    Ok(new_token) => token = new_token,
+                    Err(e) => error!("Failed to authorize again: {}", e),
+                }
+            }
+            Err(e) => {
+                error!("Failed to check streaming status: {}", e);
+                app_state.consecutive_errors += 1;
+                if let Some(rate_limited) = e.downcast_ref::<youtube::RateLimitedError>() {
+                    // Without a Retry-After header, double the wait with each failed poll
+                    let backoff = rate_limited.retry_after.unwrap_or_else(|| {
+                        config
+                            .youtube
+                            .poll_interval(false)
+                            .saturating_mul(1 << app_state.consecutive_errors.min(16))
+                            .min(MAX_RATE_LIMIT_BACKOFF)
+                    });
+                    warn!(
+                        "Rate limited, waiting {}s before the next poll",
+                        backoff.as_secs()
+                    );
+                    rate_limit_backoff = Some(backoff);
+                }
+                // Tell the user detection is broken rather than keep showing the last status
+                let stale_after = config.youtube.stale_after_errors;
+                if stale_after > 0
+                    && app_state.consecutive_errors >= stale_after
+                    && window::set_stale(&window, true)
+                {
+                    warn!(
+                        "{} status checks failed in a row, showing the frame as stale (last \
+                         known status: {:?})",
+                        app_state.consecutive_errors, current_status
+                    );
+                    if let Some(startup_show) = startup_show.take() {
+                        startup_show.abort();
+                    }
+                    show_frame(&window, quiet);
+                    if config.youtube.stale_indicator == StaleIndicator::Blink {
+                        stale_blink = Some(spawn_stale_blink(
+                            window.clone(),
+                            config.quiet_hours.clone(),
+                        ));
+                    }
+                }
+                app_state.last_error = Some(LastError {
+                    message: e.to_string(),
+                    at: Local::now(),
+                });
+            }
+        }
 
-                    current_video_id = new_video_id;
+        // Notify once per stream when the viewer count first exceeds the threshold
+        match (&current_status, config.youtube.viewer_alert_threshold) {
+            (StreamStatus::Live(stream), Some(threshold)) if !viewer_alert_shown && !mocked => {
+                match youtube::fetch_concurrent_viewers(
+                    &token.access_token,
+                    &config.youtube,
+                    &mut key_pool,
+                    &stream.video_id,
+                )
+                .await
+                {
+                    Ok(Some(viewers)) if viewers > threshold as u64 => {
+                        info!(
+                            "{} viewers are watching, above the alert threshold",
+                            viewers
+                        );
+                        window.send(WindowCommand::Notify(
+                            "liveframe".to_string(),
+                            format!("You have {} viewers watching!", viewers),
+                        ));
+                        viewer_alert_shown = true;
+                    }
+                    Ok(viewers) => debug!("Concurrent viewers: {:?}", viewers),
+                    Err(e) => warn!("Failed to get the viewer count: {}", e),
                 }
             }
-            Err(e) => error!("Failed to check streaming status: {}", e),
+            (StreamStatus::Live(_), _) => {}
+            _ => viewer_alert_shown = false,
         }
 
-        // Sleep for 5 seconds before checking again
-        tokio::time::sleep(Duration::from_secs(5)).await;
+        update_regions(&mut regions, &current_status, quiet);
+
+        window.send(WindowCommand::SetTooltip(tray::tooltip_text(
+            &current_status,
+            &app_state,
+        )));
+        poll_timings.record(poll_started.elapsed());
+
+        // Sleep for the poll interval before checking again, applying config changes meanwhile.
+        // Without continuous polling, wait for a refresh from the tray menu instead. With
+        // `watch`, a feed notification triggers the check, unless delivery has been failing.
+        let poll_interval = match &pubsub {
+            Some(pubsub) if !pubsub.fallback_polling() => PUSH_POLL_INTERVAL,
+            _ => config
+                .youtube
+                .poll_interval(matches!(current_status, StreamStatus::Live(_))),
+        }
+        .max(rate_limit_backoff.unwrap_or_default());
+        let sleep = tokio::time::sleep(poll_interval);
+        tokio::pin!(sleep);
+        let notified_poll =
+            tokio::time::sleep_until((poll_started + MIN_NOTIFIED_POLL_INTERVAL).into());
+        tokio::pin!(notified_poll);
+        let mut feed_notified = false;
+        loop {
+            tokio::select! {
+                _ = &mut sleep, if config.youtube.continuous_polling => break,
+                _ = tray::REFRESH_REQUESTED.notified() => break,
+                _ = next_feed_notification(&pubsub), if !feed_notified => {
+                    debug!("Checking the status after a feed notification");
+                    feed_notified = true;
+                }
+                _ = &mut notified_poll, if feed_notified => break,
+                // A control command only changes the visibility, so it's applied without a poll
+                _ = control::OVERRIDE_CHANGED.notified() => {
+                    let frame_override = control::frame_override();
+                    if frame_override != applied_override {
+                        applied_override = frame_override;
+                        apply_frame_override(
+                            &window,
+                            frame_override,
+                            config.quiet_hours.is_active(Local::now()),
+                            &mut startup_show,
+                        );
+                        update_regions(
+                            &mut regions,
+                            &current_status,
+                            frame_hidden(&config.quiet_hours),
+                        );
+                    }
+                }
+                new_config = next_config(&mut config_rx) => {
+                    config = new_config;
+                    if key_pool.keys() != config.youtube.api_keys.as_slice() {
+                        key_pool = QuotaKeyPool::new(config.youtube.api_keys.clone());
+                    }
+                    window::set_palette(&window, &config.frame_colors_at(Local::now()));
+                    window.send(WindowCommand::SetThickness(config.frame.thickness));
+                }
+            }
+        }
+    }
+}
+
+// Waits for the next reloaded config. Pends forever when the config isn't watched.
+async fn next_config(config_rx: &mut Option<watch::Receiver<Config>>) -> Config {
+    match config_rx {
+        Some(rx) => match rx.changed().await {
+            Ok(()) => rx.borrow_and_update().clone(),
+            Err(_) => std::future::pending().await,
+        },
+        None => std::future::pending().await,
+    }
+}
+
+// An additional frame window, shown while the streaming status is one of `show_when`
+struct Region {
+    show_when: Vec<StatusKind>,
+    window: WindowHandle,
+    visible: bool,
+}
+
+// Shows the regions configured for the current status and hides the others, all of them
+// during quiet hours
+fn update_regions(regions: &mut [Region], status: &StreamStatus, quiet: bool) {
+    for region in regions {
+        let visible = !quiet && region.show_when.contains(&status.kind());
+        if visible != region.visible {
+            region.visible = visible;
+            region.window.send(if visible {
+                WindowCommand::Show
+            } else {
+                WindowCommand::Hide
+            });
+        }
+    }
+}
+
+// Starts blinking the frame, until the returned task is aborted. The frame stays hidden during
+// quiet hours.
+fn spawn_stale_blink(
+    window: WindowHandle,
+    quiet_hours: QuietHoursConfig,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut visible = true;
+        loop {
+            tokio::time::sleep(STALE_BLINK_INTERVAL).await;
+            visible = !visible;
+            if visible {
+                show_frame(&window, frame_hidden(&quiet_hours));
+            } else {
+                window.send(WindowCommand::Hide);
+            }
+        }
+    })
+}
+
+// Shows or hides the frame for a newly set control override. Showing it ends the startup
+// grace period; going back to auto keeps the frame hidden during quiet hours and the grace
+// period.
+fn apply_frame_override(
+    window: &WindowHandle,
+    frame_override: FrameOverride,
+    quiet_hours_active: bool,
+    startup_show: &mut Option<tokio::task::JoinHandle<()>>,
+) {
+    match frame_override {
+        FrameOverride::Show => {
+            if let Some(startup_show) = startup_show.take() {
+                startup_show.abort();
+            }
+            window.send(WindowCommand::Show);
+        }
+        FrameOverride::Hide => window.send(WindowCommand::Hide),
+        FrameOverride::Auto if quiet_hours_active || startup_show.is_some() => {
+            window.send(WindowCommand::Hide)
+        }
+        FrameOverride::Auto => window.send(WindowCommand::Show),
+    }
+}
+
+// Whether the frame is hidden by a control command or quiet hours
+fn frame_hidden(quiet_hours: &QuietHoursConfig) -> bool {
+    match control::frame_override() {
+        FrameOverride::Auto => quiet_hours.is_active(Local::now()),
+        FrameOverride::Show => false,
+        FrameOverride::Hide => true,
+    }
+}
+
+// Shows the frame, unless quiet hours are active or a control command hides it
+fn show_frame(window: &WindowHandle, quiet: bool) {
+    if !quiet {
+        window.send(WindowCommand::Show);
+    }
+}
+
+// Waits for the next feed notification. Pends forever when not watching the feed.
+async fn next_feed_notification(pubsub: &Option<Arc<PubSub>>) {
+    match pubsub {
+        Some(pubsub) => pubsub.notified().await,
+        None => std::future::pending().await,
+    }
+}
+
+// Closes the frame window and waits for the window thread to finish
+fn close_window(window: &WindowHandle, window_thread: &Mutex<Option<WindowThread>>) {
+    window.send(WindowCommand::Exit);
+    let window_thread = match window_thread.lock() {
+        Ok(mut window_thread) => window_thread.take(),
+        Err(_) => None,
+    };
+    if let Some(window_thread) = window_thread {
+        let _ = window_thread.join();
+    }
+}
+
+// Shows the live frame without any OAuth or API calls, hiding it again after `duration_secs`
+// (or only on Ctrl+C without a duration)
+async fn simulate_streaming(window: &WindowHandle, duration_secs: Option<u64>) {
+    window::set_color_state(window, window::COLOR_SILENT);
+    window.send(WindowCommand::Show);
+    window.send(WindowCommand::SetTooltip(format!(
+        "liveframe: {} (simulated)",
+        StreamStatus::Live(LiveStream::default()).label()
+    )));
+
+    match duration_secs {
+        Some(duration_secs) => {
+            info!("Simulated stream ends in {}s", duration_secs);
+            tokio::time::sleep(Duration::from_secs(duration_secs)).await;
+        }
+        None => std::future::pending().await,
+    }
+
+    info!("Simulated stream ended");
+    window.send(WindowCommand::Hide);
+}
+
+// Briefly shows then hides the frame, checking that IsWindowVisible follows each command
+async fn selftest_window(window: &WindowHandle, frame: &FrameConfig) {
+    info!("Running window visibility self-test...");
+
+    // Commands are applied asynchronously by the window thread
+    let settle = Duration::from_millis(100);
+
+    window.send(WindowCommand::Show);
+    tokio::time::sleep(settle).await;
+    let shown = window.is_visible();
+    // Also lets a color transition to the initial color finish before the pixels are checked
+    tokio::time::sleep(Duration::from_millis(500 + frame.state_transition_ms)).await;
+    if shown {
+        selftest_rendering(frame);
+    }
+
+    window.send(WindowCommand::Hide);
+    tokio::time::sleep(settle).await;
+    let hidden = !window.is_visible();
+
+    if shown && hidden {
+        info!("Window visibility self-test passed");
+    } else {
+        warn!("**************************************************************");
+        warn!(
+            "Window visibility self-test FAILED (show took effect: {}, hide took effect: {})",
+            shown, hidden
+        );
+        warn!("The frame may not appear or disappear as expected");
+        warn!("**************************************************************");
+    }
+}
+
+// Checks on a screenshot of the top-left corner that the frame is drawn in the current color
+// and the inside of the frame is see-through. Only the default opaque, square, filled
+// full-screen frame with a top edge is checked.
+fn selftest_rendering(frame: &FrameConfig) {
+    if frame.style != FrameStyle::Full
+        || frame.render_mode != RenderMode::Fill
+        || frame.opacity_percent < 100
+        || frame.border_radius > 0
+        || !frame.edges.contains(&FrameEdge::Top)
+        || frame.thickness == 0
+    {
+        info!("Skipping the rendering self-test for this frame layout");
+        return;
+    }
+
+    let thickness = frame.thickness as i32;
+    let on_frame = (0, thickness / 2);
+    let inside = (thickness + 1, thickness + 1);
+    let expected = window::current_color();
+    let size = thickness + 2;
+    let pixels = match unsafe { window::capture_screen(size, size) } {
+        Ok(captured) => {
+            [on_frame, inside].map(|(x, y)| window::captured_color(&captured, size, x, y))
+        }
+        Err(e) => {
+            warn!("Rendering self-test could not capture the screen: {}", e);
+            return;
+        }
+    };
+
+    // The inside shows whatever is behind the window, which is unlikely to be the frame color
+    if pixels[0] == expected && pixels[1] != expected {
+        info!("Rendering self-test passed");
+    } else {
+        warn!("**************************************************************");
+        warn!(
+            "Rendering self-test FAILED (frame pixel {:06X}, expected {:06X}; inside pixel {:06X})",
+            pixels[0].0, expected.0, pixels[1].0
+        );
+        warn!("The frame may be drawn in the wrong color or cover the screen");
+        warn!("**************************************************************");
+    }
+}
+
+// Runs a one-shot subcommand instead of the frame
+async fn run_command(
+    command: OneShotCommand,
+    config: &Config,
+    config_path: Option<&Path>,
+) -> windows::core::Result<()> {
+    let result = match command {
+        OneShotCommand::Diagnose { output } => {
+            diagnose::run_diagnose(config, config_path, output.as_deref()).await
+        }
+        OneShotCommand::ExportConfig => {
+            config::export_config(config).map(|toml| print!("{}", toml))
+        }
+        OneShotCommand::Screenshot {
+            output,
+            keep_visible,
+        } => screenshot::take_screenshot(config, output.as_deref(), keep_visible)
+            .await
+            .map(|path| println!("Screenshot written to {}", path.display())),
+        OneShotCommand::BackupConfig { output } => {
+            backup::backup_config(config_path, output.as_deref())
+                .map(|path| println!("Backup written to {}", path.display()))
+        }
+        OneShotCommand::RestoreConfig { archive } => backup::restore_config(config_path, &archive)
+            .map(|()| println!("Restored, restart liveframe to apply the restored files")),
+        OneShotCommand::Auth => {
+            oauth::get_oauth_token(&config.oauth, true, &CancellationToken::new())
+                .await
+                .map(|_| info!("Authorization complete, the new token has been saved"))
+        }
+        OneShotCommand::Install { target } => install::run_install(&target),
+        OneShotCommand::Uninstall { target } => install::run_uninstall(&target),
+    };
+
+    exit_on_error(result)
+}
+
+// Returns the access token for one-shot commands. An access token in LIVEFRAME_ACCESS_TOKEN
+// skips the OAuth flow and the stored token entirely.
+async fn one_shot_access_token(
+    config: &Config,
+    force: bool,
+) -> std::result::Result<String, Box<dyn std::error::Error>> {
+    match std::env::var("LIVEFRAME_ACCESS_TOKEN") {
+        Ok(access_token) => {
+            info!("Using the access token from LIVEFRAME_ACCESS_TOKEN");
+            Ok(access_token)
+        }
+        Err(_) => Ok(
+            oauth::get_oauth_token(&config.oauth, force, &CancellationToken::new())
+                .await?
+                .access_token,
+        ),
+    }
+}
+
+// Checks the streaming status once and prints it, for --check-once and --once
+async fn check_once(
+    config: &Config,
+    force: bool,
+) -> std::result::Result<StreamStatus, Box<dyn std::error::Error>> {
+    let access_token = one_shot_access_token(config, force).await?;
+    let mut key_pool = QuotaKeyPool::new(config.youtube.api_keys.clone());
+    let status =
+        youtube::check_youtube_streaming(&access_token, &config.youtube, &mut key_pool).await?;
+    match &status {
+        StreamStatus::Live(stream) => println!(
+            "{}: {} (https://www.youtube.com/watch?v={})",
+            status.label(),
+            stream.title,
+            stream.video_id
+        ),
+        _ => println!("{}", status.label()),
+    }
+    Ok(status)
+}
+
+// Checks the streaming status once like --check-once and, if a stream is live, shows the live
+// frame for `duration_secs` before exiting, for --once
+async fn check_once_and_show(
+    config: &Config,
+    force: bool,
+    duration_secs: u64,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let status = check_once(config, force).await?;
+    if !matches!(status, StreamStatus::Live(_)) || duration_secs == 0 {
+        return Ok(());
+    }
+
+    let (window, window_thread) =
+        window::spawn_frame_window(config.frame.clone(), WindowRole::Main)?;
+    window::set_palette(&window, &config.frame_colors_at(Local::now()));
+    window::set_color_state(&window, window::COLOR_SILENT);
+    window.send(WindowCommand::SetTooltip(tray::tooltip_text(
+        &status,
+        &AppState::default(),
+    )));
+    window.send(WindowCommand::Show);
+    tokio::time::sleep(Duration::from_secs(duration_secs)).await;
+
+    window.send(WindowCommand::Exit);
+    let _ = window_thread.join();
+    Ok(())
+}
+
+// Prints the user's active, upcoming and completed broadcasts as the API reports them, for
+// --list-broadcasts
+async fn list_broadcasts(
+    config: &Config,
+    force: bool,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let access_token = one_shot_access_token(config, force).await?;
+    let mut key_pool = QuotaKeyPool::new(config.youtube.api_keys.clone());
+
+    println!(
+        "{:<11} {:<13} {:<14} {:<9} TITLE",
+        "STATUS", "ID", "LIFECYCLE", "PRIVACY"
+    );
+    for broadcast_status in ["active", "upcoming", "completed"] {
+        let broadcasts = youtube::list_broadcasts(
+            &access_token,
+            &config.youtube,
+            &mut key_pool,
+            broadcast_status,
+        )
+        .await?;
+        for broadcast in broadcasts {
+            println!(
+                "{:<11} {:<13} {:<14} {:<9} {}",
+                broadcast_status,
+                broadcast.id,
+                broadcast.status.life_cycle_status.as_deref().unwrap_or("-"),
+                broadcast.status.privacy_status.as_deref().unwrap_or("-"),
+                broadcast.snippet.title
+            );
+        }
+    }
+    Ok(())
+}
+
+// Logs a failed one-shot command and turns it into the exit error
+fn exit_on_error(
+    result: std::result::Result<(), Box<dyn std::error::Error>>,
+) -> windows::core::Result<()> {
+    if let Err(e) = result {
+        error!("Command failed: {}", e);
+        return Err(windows::core::Error::from_win32());
     }
+    Ok(())
 }
@@ -0,0 +1,207 @@
+use std::sync::atomic::{fence, AtomicU32, Ordering};
+
+use log::{debug, info};
+use windows::core::*;
+use windows::Win32::Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE};
+use windows::Win32::System::Memory::{
+    CreateFileMappingW, MapViewOfFile, UnmapViewOfFile, FILE_MAP_WRITE, MEMORYMAPPEDVIEW_HANDLE,
+    PAGE_READWRITE,
+};
+
+// Name of the shared memory section other processes (e.g. an OBS script) can open
+pub const MIRROR_SECTION_NAME: PCWSTR = w!("Local\\liveframe_frame");
+
+// The section starts with a header of little-endian u32 values: width, height and a
+// sequence number that is incremented after every update, followed by width * height
+// BGRA pixels, top row first.
+const HEADER_SIZE: usize = 12;
+
+// Named shared memory section holding a copy of the rendered frame
+pub struct FrameMirror {
+    mapping: HANDLE,
+    view: MEMORYMAPPEDVIEW_HANDLE,
+    width: u32,
+    height: u32,
+    sequence: u32,
+    // Render buffer reused across updates, so repaints don't allocate a frame-sized buffer
+    buffer: Vec<u8>,
+}
+
+impl FrameMirror {
+    // Creates the shared memory section sized for a frame of the given dimensions
+    pub unsafe fn create(width: i32, height: i32) -> Result<Self> {
+        let (width, height) = (width.max(0) as u32, height.max(0) as u32);
+        let size = HEADER_SIZE + width as usize * height as usize * 4;
+
+        let mapping = CreateFileMappingW(
+            INVALID_HANDLE_VALUE,
+            None,
+            PAGE_READWRITE,
+            0,
+            size as u32,
+            MIRROR_SECTION_NAME,
+        )?;
+        let view = match MapViewOfFile(mapping, FILE_MAP_WRITE, 0, 0, size) {
+            Ok(view) => view,
+            Err(e) => {
+                CloseHandle(mapping);
+                return Err(e);
+            }
+        };
+
+        info!(
+            "Mirroring the frame to shared memory section {} ({}x{})",
+            MIRROR_SECTION_NAME.display(),
+            width,
+            height
+        );
+        Ok(Self {
+            mapping,
+            view,
+            width,
+            height,
+            sequence: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    // Renders into the reusable buffer with `render` (see render_frame_to_buffer) and copies the
+    // result into the section
+    pub fn update(&mut self, render: impl FnOnce(&mut Vec<u8>)) {
+        let mut pixels = std::mem::take(&mut self.buffer);
+        render(&mut pixels);
+        self.write(&pixels);
+        self.buffer = pixels;
+    }
+
+    // Copies a rendered buffer into the section. Buffers for other dimensions are ignored,
+    // since the section can't be resized.
+    fn write(&mut self, pixels: &[u8]) {
+        if pixels.len() != self.width as usize * self.height as usize * 4 {
+            debug!("Frame size changed, not updating the shared memory mirror");
+            return;
+        }
+
+        self.sequence = self.sequence.wrapping_add(1);
+        let base = self.view.0 as *mut u8;
+        unsafe {
+            for (offset, value) in [self.width, self.height, self.sequence].iter().enumerate() {
+                let bytes = value.to_le_bytes();
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), base.add(offset * 4), 4);
+            }
+            std::ptr::copy_nonoverlapping(pixels.as_ptr(), base.add(HEADER_SIZE), pixels.len());
+        }
+    }
+}
+
+impl Drop for FrameMirror {
+    fn drop(&mut self) {
+        unsafe {
+            UnmapViewOfFile(self.view);
+            CloseHandle(self.mapping);
+        }
+    }
+}
+
+// Name of the shared memory section holding the streaming status for other processes
+pub const STATUS_SECTION_NAME: PCWSTR = w!("Local\\liveframe_status");
+
+// Layout of the status section, all values little-endian:
+//   0: u32 streaming flag (1 while live), updated atomically
+//   4: u32 sequence number, odd while the fields below are being written
+//   8: i64 Unix timestamp (seconds) of the last update
+//  16: u32 length of the title in bytes
+//  20: UTF-8 title of the live broadcast, truncated to STATUS_TITLE_SIZE bytes
+//
+// The fields from offset 8 on are guarded by the sequence number (a seqlock). Readers load the
+// sequence with Acquire ordering and retry while it's odd, copy the fields, then issue an Acquire
+// fence and load the sequence again, retrying if it changed.
+const STATUS_STREAMING_OFFSET: usize = 0;
+const STATUS_SEQUENCE_OFFSET: usize = 4;
+const STATUS_TIMESTAMP_OFFSET: usize = 8;
+const STATUS_TITLE_LEN_OFFSET: usize = 16;
+const STATUS_TITLE_OFFSET: usize = 20;
+const STATUS_TITLE_SIZE: usize = 256;
+const STATUS_SIZE: usize = STATUS_TITLE_OFFSET + STATUS_TITLE_SIZE;
+
+// Named shared memory section holding the current streaming status
+pub struct StatusMirror {
+    mapping: HANDLE,
+    view: MEMORYMAPPEDVIEW_HANDLE,
+    sequence: u32,
+}
+
+impl StatusMirror {
+    pub unsafe fn create() -> Result<Self> {
+        let mapping = CreateFileMappingW(
+            INVALID_HANDLE_VALUE,
+            None,
+            PAGE_READWRITE,
+            0,
+            STATUS_SIZE as u32,
+            STATUS_SECTION_NAME,
+        )?;
+        let view = match MapViewOfFile(mapping, FILE_MAP_WRITE, 0, 0, STATUS_SIZE) {
+            Ok(view) => view,
+            Err(e) => {
+                CloseHandle(mapping);
+                return Err(e);
+            }
+        };
+
+        info!(
+            "Publishing the streaming status to shared memory section {}",
+            STATUS_SECTION_NAME.display()
+        );
+        Ok(Self {
+            mapping,
+            view,
+            sequence: 0,
+        })
+    }
+
+    // Writes the status. Readers retry while the sequence number is odd or changed during
+    // their read; the streaming flag alone can be read at any time.
+    pub fn write(&mut self, streaming: bool, title: &str, timestamp: i64) {
+        let base = self.view.0 as *mut u8;
+        // Truncate on a character boundary
+        let mut title_len = title.len().min(STATUS_TITLE_SIZE);
+        while !title.is_char_boundary(title_len) {
+            title_len -= 1;
+        }
+
+        unsafe {
+            let streaming_flag = &*(base.add(STATUS_STREAMING_OFFSET) as *const AtomicU32);
+            let sequence = &*(base.add(STATUS_SEQUENCE_OFFSET) as *const AtomicU32);
+
+            self.sequence = self.sequence.wrapping_add(1);
+            sequence.store(self.sequence, Ordering::Release);
+            // A Release store only orders the writes before it, so without the fence the field
+            // writes below could become visible before the odd sequence number
+            fence(Ordering::Release);
+
+            let timestamp = timestamp.to_le_bytes();
+            std::ptr::copy_nonoverlapping(
+                timestamp.as_ptr(),
+                base.add(STATUS_TIMESTAMP_OFFSET),
+                timestamp.len(),
+            );
+            let len = (title_len as u32).to_le_bytes();
+            std::ptr::copy_nonoverlapping(len.as_ptr(), base.add(STATUS_TITLE_LEN_OFFSET), 4);
+            std::ptr::copy_nonoverlapping(title.as_ptr(), base.add(STATUS_TITLE_OFFSET), title_len);
+            streaming_flag.store(streaming as u32, Ordering::Release);
+
+            self.sequence = self.sequence.wrapping_add(1);
+            sequence.store(self.sequence, Ordering::Release);
+        }
+    }
+}
+
+impl Drop for StatusMirror {
+    fn drop(&mut self) {
+        unsafe {
+            UnmapViewOfFile(self.view);
+            CloseHandle(self.mapping);
+        }
+    }
+}
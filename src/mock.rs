@@ -0,0 +1,207 @@
+use std::fs;
+use std::path::Path;
+
+use log::info;
+
+use crate::models::{LiveStream, StreamStatus};
+
+// Status source replacing the YouTube API with a scripted timeline, to exercise the main loop
+// (confirmation and grace periods, quiet hours, colors) without Google.
+//
+// The script has one step per line: a status (`offline`, `pre_show`, `ready`,
+// `encoder_connected`, `live` or `error` for a failed poll), optionally followed by the number
+// of polls it lasts (default 1). Empty lines and lines starting with `#` are ignored. Once the
+// script is exhausted the last step repeats.
+pub struct MockStreamProvider {
+    steps: Vec<Option<StreamStatus>>,
+    next: usize,
+}
+
+impl MockStreamProvider {
+    // Only the test build compiles this module without the feature
+    #[cfg_attr(not(feature = "mock-provider"), allow(dead_code))]
+    pub fn from_file(path: &Path) -> std::result::Result<Self, Box<dyn std::error::Error>> {
+        let provider = Self::parse(&fs::read_to_string(path)?)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        info!(
+            "Loaded a mock status script with {} polls from {}",
+            provider.steps.len(),
+            path.display()
+        );
+        Ok(provider)
+    }
+
+    // Parses the script itself
+    fn parse(contents: &str) -> std::result::Result<Self, Box<dyn std::error::Error>> {
+        let mut steps = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let status = match fields.next() {
+                Some("offline") => Some(StreamStatus::Offline),
+                Some("pre_show") => Some(StreamStatus::PreShow),
+                Some("ready") => Some(StreamStatus::Ready),
+                Some("encoder_connected") => Some(StreamStatus::EncoderConnected),
+                Some("live") => Some(StreamStatus::Live(LiveStream {
+                    video_id: "mock".to_string(),
+                    title: "Mock stream".to_string(),
+                    ..Default::default()
+                })),
+                Some("error") => None,
+                other => {
+                    return Err(format!("Line {}: unknown status {:?}", i + 1, other).into());
+                }
+            };
+            let polls = match fields.next() {
+                Some(polls) => polls
+                    .parse::<usize>()
+                    .map_err(|e| format!("Line {}: invalid poll count: {}", i + 1, e))?,
+                None => 1,
+            };
+            steps.extend(std::iter::repeat_n(status, polls));
+        }
+
+        if steps.is_empty() {
+            return Err("the script contains no statuses".into());
+        }
+        Ok(Self { steps, next: 0 })
+    }
+
+    // Returns the scripted result of the next poll
+    pub fn next_status(&mut self) -> std::result::Result<StreamStatus, Box<dyn std::error::Error>> {
+        let step = &self.steps[self.next.min(self.steps.len() - 1)];
+        self.next += 1;
+        info!("Mock poll #{}: {:?}", self.next, step);
+        step.clone().ok_or_else(|| "Scripted API error".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::debounce::StatusDebouncer;
+    use crate::models::StatusKind;
+    use crate::transition::{self, FrameAction};
+    use crate::window::{COLOR_IDLE, COLOR_READY, COLOR_UNKNOWN};
+
+    // Runs the script through the status derivation of the main loop, one poll every 10s.
+    // Returns the status after each poll, and the frame actions of the status changes.
+    // A failed poll keeps the current status.
+    fn run_script(
+        script: &str,
+        polls: usize,
+        quiet: bool,
+    ) -> (Vec<StreamStatus>, Vec<FrameAction>) {
+        let mut provider = MockStreamProvider::parse(script).unwrap();
+        let clock = FakeClock::new();
+        let mut debouncer = StatusDebouncer::default();
+        let confirm = Duration::from_secs(20);
+        let grace = Duration::from_secs(30);
+
+        let mut current = StreamStatus::Offline;
+        let mut statuses = Vec::new();
+        let mut actions = Vec::new();
+        for _ in 0..polls {
+            if let Ok(polled) = provider.next_status() {
+                let new_status = debouncer.apply(&current, polled, confirm, grace, &clock);
+                if new_status != current {
+                    actions.extend(transition::status_change_actions(&new_status, quiet));
+                    current = new_status;
+                }
+            }
+            statuses.push(current.clone());
+            clock.advance(Duration::from_secs(10));
+        }
+        (statuses, actions)
+    }
+
+    #[test]
+    fn scripted_stream_goes_live_and_ends_after_the_grace_period() {
+        let script = "
+            # warm-up
+            pre_show
+            ready
+            encoder_connected
+            live 4
+            error
+            offline
+        ";
+
+        let kinds: Vec<StatusKind> = run_script(script, 12, false)
+            .0
+            .iter()
+            .map(StreamStatus::kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                StatusKind::PreShow,
+                StatusKind::Ready,
+                StatusKind::EncoderConnected,
+                // Live from the third live poll, 20s after the first
+                StatusKind::EncoderConnected,
+                StatusKind::EncoderConnected,
+                StatusKind::Live,
+                StatusKind::Live,
+                // The failed poll keeps the status
+                StatusKind::Live,
+                // Offline only once the 30s grace period has passed
+                StatusKind::Live,
+                StatusKind::Live,
+                StatusKind::Live,
+                StatusKind::Offline,
+            ]
+        );
+    }
+
+    #[test]
+    fn scripted_stream_shows_and_colors_the_frame() {
+        let script = "
+            ready
+            live 3
+            offline
+        ";
+
+        let (_, actions) = run_script(script, 8, false);
+        assert_eq!(
+            actions,
+            [
+                FrameAction::SetColorState(COLOR_UNKNOWN),
+                FrameAction::SetColorState(COLOR_READY),
+                FrameAction::Show,
+                // Live: the audio task sets the color later
+                FrameAction::SetColorState(COLOR_UNKNOWN),
+                FrameAction::Show,
+                // Ended after the grace period
+                FrameAction::SetColorState(COLOR_UNKNOWN),
+                FrameAction::SetColorState(COLOR_IDLE),
+                FrameAction::Show,
+            ]
+        );
+    }
+
+    #[test]
+    fn frame_is_not_shown_during_quiet_hours() {
+        let (_, actions) = run_script("live 3\noffline", 8, true);
+        assert!(!actions.contains(&FrameAction::Show));
+        assert_eq!(
+            actions.last(),
+            Some(&FrameAction::SetColorState(COLOR_IDLE))
+        );
+    }
+
+    #[test]
+    fn unknown_status_is_rejected() {
+        let error = MockStreamProvider::parse("offline\nstreaming 2\n")
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("Line 2"));
+    }
+}
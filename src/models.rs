@@ -1,19 +1,27 @@
-use chrono::{DateTime, Utc};
+// The fuzz targets include this file on its own, so it must not use other liveframe modules
+use chrono::{DateTime, Local, Utc};
 use oauth2::PkceCodeVerifier;
 use serde::{Deserialize, Serialize};
 
-// Struct for OAuth client secrets
+// Struct for OAuth client secrets. Desktop app credentials are under "installed", web
+// application credentials under "web".
 #[derive(Deserialize)]
 pub struct ClientSecrets {
-    pub installed: InstalledSecrets,
+    #[serde(default)]
+    pub installed: Option<InstalledSecrets>,
+    #[serde(default)]
+    pub web: Option<InstalledSecrets>,
 }
 
-#[derive(Deserialize)]
+// Client credentials, with the same fields for both credential types
+#[derive(Deserialize, Clone)]
 pub struct InstalledSecrets {
     pub client_id: String,
     pub client_secret: String,
     pub auth_uri: String,
     pub token_uri: String,
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
 }
 
 // Struct for OAuth tokens
@@ -22,12 +30,34 @@ pub struct TokenInfo {
     pub access_token: String,
     pub refresh_token: String,
     pub expiry: DateTime<Utc>,
+    // Scopes granted to the token. Empty if unknown (e.g. tokens saved by older versions).
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+impl TokenInfo {
+    // Returns the required scopes the token wasn't granted. Nothing is reported missing when
+    // the granted scopes are unknown.
+    pub fn missing_scopes<'a>(&self, required: &'a [String]) -> Vec<&'a str> {
+        if self.scopes.is_empty() {
+            return Vec::new();
+        }
+        required
+            .iter()
+            .filter(|scope| !self.scopes.contains(scope))
+            .map(String::as_str)
+            .collect()
+    }
 }
 
 // Struct for YouTube API response
 #[derive(Deserialize)]
 pub struct LiveBroadcastsResponse {
+    #[serde(default)]
     pub items: Vec<LiveBroadcast>,
+    #[serde(default)]
+    #[serde(rename = "nextPageToken")]
+    pub next_page_token: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -39,7 +69,96 @@ pub struct LiveBroadcast {
 
 #[derive(Deserialize)]
 pub struct LiveBroadcastSnippet {
+    // Not every broadcast reports a title (e.g. some membership-only streams)
+    #[serde(default)]
     pub title: String,
+    #[serde(default)]
+    #[serde(rename = "scheduledStartTime")]
+    pub scheduled_start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub thumbnails: Option<Thumbnails>,
+    #[serde(default)]
+    #[serde(rename = "actualStartTime")]
+    pub actual_start_time: Option<DateTime<Utc>>,
+    // Name of the channel the broadcast belongs to
+    #[serde(default)]
+    #[serde(rename = "channelTitle")]
+    pub channel_name: Option<String>,
+}
+
+impl LiveBroadcastSnippet {
+    // URL of the default (120x90) thumbnail, if the broadcast has one
+    pub fn default_thumbnail_url(&self) -> Option<String> {
+        self.thumbnails
+            .as_ref()
+            .and_then(|thumbnails| thumbnails.default.as_ref())
+            .map(|thumbnail| thumbnail.url.clone())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Thumbnails {
+    #[serde(default)]
+    pub default: Option<Thumbnail>,
+}
+
+#[derive(Deserialize)]
+pub struct Thumbnail {
+    pub url: String,
+}
+
+// Response of videos.list with part=liveStreamingDetails
+#[derive(Deserialize)]
+pub struct VideosResponse {
+    #[serde(default)]
+    pub items: Vec<Video>,
+}
+
+#[derive(Deserialize)]
+pub struct Video {
+    #[serde(default)]
+    #[serde(rename = "liveStreamingDetails")]
+    pub live_streaming_details: Option<LiveStreamingDetails>,
+}
+
+#[derive(Deserialize)]
+pub struct LiveStreamingDetails {
+    // Sent as a string, and left out when the broadcast hides its viewer count
+    #[serde(default)]
+    #[serde(rename = "concurrentViewers")]
+    pub concurrent_viewers: Option<String>,
+}
+
+// Response of liveStreams.list with part=status. A live stream is the ingest point the encoder
+// sends video to, separate from the broadcast it is bound to.
+#[derive(Deserialize)]
+pub struct LiveStreamsResponse {
+    #[serde(default)]
+    pub items: Vec<LiveStreamResource>,
+}
+
+#[derive(Deserialize)]
+pub struct LiveStreamResource {
+    pub id: String,
+    #[serde(default)]
+    pub status: Option<LiveStreamResourceStatus>,
+}
+
+#[derive(Deserialize)]
+pub struct LiveStreamResourceStatus {
+    // active, created, error, inactive or ready
+    #[serde(default)]
+    #[serde(rename = "streamStatus")]
+    pub stream_status: Option<String>,
+}
+
+impl LiveStreamResource {
+    // Whether an encoder is currently sending data to the ingest point
+    pub fn is_receiving(&self) -> bool {
+        self.status
+            .as_ref()
+            .is_some_and(|status| status.stream_status.as_deref() == Some("active"))
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -47,6 +166,81 @@ pub struct LiveBroadcastStatus {
     #[serde(default)]
     #[serde(rename = "lifeCycleStatus")]
     pub life_cycle_status: Option<String>,
+    // public, unlisted or private. Membership-only streams are reported as public or unlisted,
+    // the membership gate itself isn't exposed by liveBroadcasts.
+    #[serde(default)]
+    #[serde(rename = "privacyStatus")]
+    pub privacy_status: Option<String>,
+}
+
+impl LiveBroadcastStatus {
+    // Whether the broadcast is on air. `liveStarting` is the short transition into `live`.
+    pub fn is_live(&self) -> bool {
+        matches!(
+            self.life_cycle_status.as_deref(),
+            Some("live") | Some("liveStarting")
+        )
+    }
+}
+
+// Streaming status derived from the user's broadcasts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StreamStatus {
+    Offline,
+    // An upcoming broadcast is scheduled to start soon
+    PreShow,
+    // A broadcast is set up and ready, but not live yet
+    Ready,
+    // An encoder is sending to an ingest point, but no broadcast is live yet
+    EncoderConnected,
+    // Live, with the tracked broadcast
+    Live(LiveStream),
+}
+
+// The live broadcast being tracked, with what notifications need to show it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LiveStream {
+    // The YouTube broadcast ID, which is identical to the video ID
+    pub video_id: String,
+    pub title: String,
+    pub thumbnail_url: Option<String>,
+    // When the broadcast actually went live, as reported by the API
+    pub started_at: Option<DateTime<Utc>>,
+    // Name of the broadcasting channel, if known
+    pub channel_name: Option<String>,
+}
+
+impl StreamStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            StreamStatus::Offline => "Not streaming",
+            StreamStatus::PreShow => "Starting soon",
+            StreamStatus::Ready => "Ready to go live",
+            StreamStatus::EncoderConnected => "Encoder connected",
+            StreamStatus::Live(_) => "Live",
+        }
+    }
+
+    pub fn kind(&self) -> StatusKind {
+        match self {
+            StreamStatus::Offline => StatusKind::Offline,
+            StreamStatus::PreShow => StatusKind::PreShow,
+            StreamStatus::Ready => StatusKind::Ready,
+            StreamStatus::EncoderConnected => StatusKind::EncoderConnected,
+            StreamStatus::Live(_) => StatusKind::Live,
+        }
+    }
+}
+
+// Streaming statuses, as named in the config file
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusKind {
+    Offline,
+    PreShow,
+    Ready,
+    EncoderConnected,
+    Live,
 }
 
 // Global state for the OAuth callback server
@@ -55,4 +249,78 @@ pub struct OAuthState {
     pub csrf_state: String,
     pub pkce_verifier: Option<PkceCodeVerifier>,
     pub auth_code_received_tx: Option<tokio::sync::oneshot::Sender<()>>,
+    // Where the browser is redirected after a successful callback, instead of the success page
+    pub success_redirect_url: Option<String>,
+}
+
+// Authorization started by --print-auth-url, waiting for --complete-auth
+#[derive(Serialize, Deserialize)]
+pub struct PendingAuth {
+    pub csrf_state: String,
+    pub pkce_verifier: String,
+    pub redirect_uri: String,
+}
+
+// Application state surfaced to the user (tray tooltip) for troubleshooting
+#[derive(Default)]
+pub struct AppState {
+    // Most recent polling error, cleared on the next successful poll
+    pub last_error: Option<LastError>,
+    // Number of polls that failed in a row, reset on the next successful poll
+    pub consecutive_errors: u32,
+}
+
+pub struct LastError {
+    pub message: String,
+    pub at: DateTime<Local>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broadcast_without_title_or_life_cycle_status_is_parsed() {
+        let json = r#"{
+            "items": [
+                {
+                    "id": "members-only",
+                    "snippet": {"channelTitle": "Channel"},
+                    "status": {"privacyStatus": "public"}
+                }
+            ]
+        }"#;
+
+        let response: LiveBroadcastsResponse = serde_json::from_str(json).unwrap();
+        let broadcast = &response.items[0];
+        assert_eq!(broadcast.id, "members-only");
+        assert_eq!(broadcast.snippet.title, "");
+        assert_eq!(broadcast.status.life_cycle_status, None);
+        assert!(!broadcast.status.is_live());
+        assert_eq!(response.next_page_token, None);
+    }
+
+    #[test]
+    fn live_starting_broadcast_is_live() {
+        let json = r#"{
+            "items": [
+                {
+                    "id": "starting",
+                    "snippet": {
+                        "title": "Starting soon",
+                        "actualStartTime": "2024-05-01T12:00:00Z"
+                    },
+                    "status": {"lifeCycleStatus": "liveStarting", "privacyStatus": "public"}
+                }
+            ]
+        }"#;
+
+        let response: LiveBroadcastsResponse = serde_json::from_str(json).unwrap();
+        let broadcast = &response.items[0];
+        assert!(broadcast.status.is_live());
+        assert_eq!(
+            broadcast.snippet.actual_start_time,
+            Some("2024-05-01T12:00:00Z".parse().unwrap())
+        );
+    }
 }
@@ -1,33 +1,88 @@
 use std::collections::HashMap;
 use std::fs::{self, File};
+use std::future::Future;
 use std::io::{Read, Write};
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, Once};
 use std::time::Duration;
 
 use chrono::Utc;
 use dirs::home_dir;
+use hyper::server::conn::Http;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Request, Response, Server, StatusCode};
 use log::{debug, error, info, warn};
-use oauth2::basic::{BasicClient, BasicErrorResponseType};
+use oauth2::basic::{
+    BasicClient, BasicErrorResponseType, BasicRequestTokenError, BasicTokenResponse,
+};
 use oauth2::{
-    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge, RedirectUrl,
-    RefreshToken, Scope, TokenResponse, TokenUrl,
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge,
+    PkceCodeVerifier, RedirectUrl, RefreshToken, Scope, TokenResponse, TokenUrl,
 };
+use tokio::net::TcpListener;
 use tokio::sync::oneshot;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
 use windows::core::*;
 use windows::Win32::UI::Shell::ShellExecuteW;
 use windows::Win32::UI::WindowsAndMessaging::SW_SHOW;
 
-use crate::models::{ClientSecrets, OAuthState, TokenInfo};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{get_liveframe_dir, write_atomic};
+use crate::config::{ConfigError, OAuthConfig, StorageBackend};
+use crate::credential;
+use crate::models::{ClientSecrets, InstalledSecrets, OAuthState, PendingAuth, TokenInfo};
+use crate::retry::{RetryBudgetExhausted, RETRY_BUDGET};
 
 // Maximum number of retries for network operations
 const MAX_RETRIES: u32 = 3;
 // Delay between retries in seconds
 const RETRY_DELAY: u64 = 5;
+// Environment variable holding the token as JSON, for environments that can't store a token file
+const TOKEN_ENV_VAR: &str = "LIVEFRAME_TOKEN";
+// Environment variable holding the contents of secret.json, for environments without the file
+const SECRET_ENV_VAR: &str = "LIVEFRAME_SECRET";
+// Port of the local OAuth callback server when the registered redirect URI doesn't specify one
+const DEFAULT_CALLBACK_PORT: u16 = 8080;
+// Redirect URI of the copy-paste flow, where Google shows the code instead of redirecting
+const OUT_OF_BAND_REDIRECT_URI: &str = "urn:ietf:wg:oauth:2.0:oob";
+// Page shown in the browser by the OAuth callback server
+const CALLBACK_PAGE_TEMPLATE: &str = include_str!("oauth_page.html");
+// Closes the tab a few seconds after a successful authorization. Browsers only allow it for
+// some tabs, hence the hint to close it manually.
+const AUTO_CLOSE_SCRIPT: &str = "<script>setTimeout(() => window.close(), 3000);</script>";
+
+// Error that retrying can't fix, such as a revoked refresh token or invalid client credentials
+#[derive(Debug)]
+pub struct NonRetriableError(pub String);
+
+impl std::fmt::Display for NonRetriableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for NonRetriableError {}
+
+// Returns whether a token endpoint error may succeed on retry. Network errors and unparseable
+// (e.g. 5xx) responses are retriable; rejected grants and client credentials are not.
+fn is_retriable_token_error<RE: std::error::Error + 'static>(
+    e: &BasicRequestTokenError<RE>,
+) -> bool {
+    match e {
+        oauth2::RequestTokenError::ServerResponse(response) => !matches!(
+            response.error(),
+            BasicErrorResponseType::InvalidGrant
+                | BasicErrorResponseType::InvalidClient
+                | BasicErrorResponseType::UnauthorizedClient
+        ),
+        _ => true,
+    }
+}
 
-// Generic retry function for async operations
+// Generic retry function for async operations. A NonRetriableError fails immediately, as does
+// running out of the shared retry budget.
 pub async fn retry_async<T, F, Fut, E>(
     operation_name: &str,
     f: F,
@@ -35,13 +90,18 @@ pub async fn retry_async<T, F, Fut, E>(
 where
     F: Fn() -> Fut,
     Fut: std::future::Future<Output = std::result::Result<T, E>>,
-    E: std::fmt::Display + 'static,
+    E: Into<Box<dyn std::error::Error>>,
 {
     let mut retry_count = 0;
     loop {
         match f().await {
             Ok(result) => return Ok(result),
             Err(e) => {
+                let e = e.into();
+                if e.is::<NonRetriableError>() {
+                    return Err(e);
+                }
+
                 retry_count += 1;
                 if retry_count >= MAX_RETRIES {
                     return Err(format!(
@@ -50,6 +110,9 @@ where
                     )
                     .into());
                 }
+                if !RETRY_BUDGET.try_acquire() {
+                    return Err(RetryBudgetExhausted(operation_name.to_string()).into());
+                }
 
                 warn!(
                     "Error during {} (attempt {}/{}): {}",
@@ -90,28 +153,41 @@ pub fn open_url_in_browser(url: &str) -> std::result::Result<(), Box<dyn std::er
     Ok(())
 }
 
-// Function to get OAuth token (either from file or through auth flow)
-pub async fn get_oauth_token() -> std::result::Result<TokenInfo, Box<dyn std::error::Error>> {
-    // Check if token file exists
-    let token_path = get_token_path()?;
-    if token_path.exists() {
-        info!("Found existing token file, loading...");
-        let mut file = File::open(&token_path)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-
-        let token_info: TokenInfo = serde_json::from_str(&contents)?;
-
+// Function to get OAuth token (either from storage or through auth flow)
+pub async fn get_oauth_token(
+    config: &OAuthConfig,
+    force: bool,
+    cancel: &CancellationToken,
+) -> std::result::Result<TokenInfo, Box<dyn std::error::Error>> {
+    let stored = if force {
+        info!("Re-authorization requested, ignoring any stored token");
+        None
+    } else {
+        load_token(config)?
+    };
+    let stored = stored.filter(|token_info| {
+        let missing = token_info.missing_scopes(&config.scopes);
+        if !missing.is_empty() {
+            info!(
+                "Stored token lacks the scopes {}, authorizing again",
+                missing.join(", ")
+            );
+        }
+        missing.is_empty()
+    });
+    if let Some(token_info) = stored {
         // If token is not expired, return it
-        if Utc::now() < token_info.expiry {
+        if !token_needs_refresh(config, &token_info, &SystemClock) {
             debug!("Token is still valid, using existing token");
             return Ok(token_info);
         }
 
         // If token is expired, try to refresh it with retry logic
         info!("Token expired, refreshing...");
-        match refresh_token(&token_info.refresh_token).await {
+        match refresh_token(config, &token_info.refresh_token).await {
             Ok(new_token) => return Ok(new_token),
+            // Opening the browser wouldn't help while the network keeps failing
+            Err(e) if e.is::<RetryBudgetExhausted>() => return Err(e),
             Err(e) => {
                 warn!("Failed to refresh token: {}, starting new auth flow", e);
             }
@@ -120,15 +196,95 @@ pub async fn get_oauth_token() -> std::result::Result<TokenInfo, Box<dyn std::er
 
     // If no valid token exists or refresh failed, start OAuth flow with retry logic
     info!("Starting OAuth authentication flow...");
-    let token_info = retry_async("complete OAuth flow", || oauth_flow()).await?;
+    let token_info = retry_async("complete OAuth flow", || oauth_flow(config, cancel)).await?;
 
-    // Save token to file
-    save_token(&token_info)?;
-    debug!("Token saved to file");
+    save_token(config, &token_info)?;
 
     Ok(token_info)
 }
 
+// Returns whether the token has expired or will within the configured safety margin
+pub fn token_needs_refresh(
+    config: &OAuthConfig,
+    token_info: &TokenInfo,
+    clock: &dyn Clock,
+) -> bool {
+    let now = clock.now();
+    if now >= token_info.expiry {
+        return true;
+    }
+    let margin = chrono::Duration::seconds(config.expiry_margin_secs as i64);
+    if now >= token_info.expiry - margin {
+        info!(
+            "Token expires at {}, within the {}s safety margin; refreshing early",
+            token_info.expiry, config.expiry_margin_secs
+        );
+        return true;
+    }
+    false
+}
+
+// Function to load the stored token from the configured backend. Credential Manager falls back
+// to the token file, which also picks up a token saved before switching backends.
+// The LIVEFRAME_TOKEN environment variable takes precedence over both.
+pub fn load_token(
+    config: &OAuthConfig,
+) -> std::result::Result<Option<TokenInfo>, Box<dyn std::error::Error>> {
+    if let Ok(json) = std::env::var(TOKEN_ENV_VAR) {
+        info!(
+            "Loading token from the {} environment variable",
+            TOKEN_ENV_VAR
+        );
+        let token_info = serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to parse {}: {}", TOKEN_ENV_VAR, e))?;
+        return Ok(Some(token_info));
+    }
+
+    if config.storage_backend == StorageBackend::CredentialManager {
+        match credential::read_token() {
+            Ok(Some(token_info)) => {
+                info!("Loaded token from Credential Manager");
+                return Ok(Some(token_info));
+            }
+            Ok(None) => debug!("No token in Credential Manager, checking the token file"),
+            Err(e) => warn!(
+                "Failed to read token from Credential Manager ({}), checking the token file",
+                e
+            ),
+        }
+    }
+
+    let token_path = get_token_path()?;
+    if !token_path.exists() {
+        return Ok(None);
+    }
+    info!("Found existing token file, loading...");
+    load_token_file(&token_path)
+}
+
+// Function to load the token file. A corrupted file (e.g. truncated by an unclean shutdown)
+// is deleted and None is returned so a fresh auth flow can replace it.
+pub fn load_token_file(
+    token_path: &Path,
+) -> std::result::Result<Option<TokenInfo>, Box<dyn std::error::Error>> {
+    let mut file = File::open(token_path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    match serde_json::from_str(&contents) {
+        Ok(token_info) => Ok(Some(token_info)),
+        Err(e) => {
+            warn!(
+                "Token file {} is corrupted ({}), deleting it and starting a new auth flow",
+                token_path.display(),
+                e
+            );
+            fs::remove_file(token_path)?;
+            Ok(None)
+        }
+    }
+}
+
 // Function to get the path to the token file
 pub fn get_token_path() -> std::result::Result<PathBuf, Box<dyn std::error::Error>> {
     let mut path = home_dir().ok_or("Could not find home directory")?;
@@ -156,54 +312,157 @@ pub fn get_secrets_path() -> std::result::Result<PathBuf, Box<dyn std::error::Er
     Ok(path)
 }
 
-// Function to save token to file
-pub fn save_token(token_info: &TokenInfo) -> std::result::Result<(), Box<dyn std::error::Error>> {
+// Function to save token to the configured backend, falling back to the token file if
+// Credential Manager fails. A token that came from LIVEFRAME_TOKEN is printed to stdout
+// instead, so the environment can be updated with it.
+pub fn save_token(
+    config: &OAuthConfig,
+    token_info: &TokenInfo,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    if std::env::var_os(TOKEN_ENV_VAR).is_some() {
+        info!(
+            "Token was loaded from {}, printing the new token instead of saving it",
+            TOKEN_ENV_VAR
+        );
+        println!("{}", serde_json::to_string(token_info)?);
+        return Ok(());
+    }
+
+    if config.storage_backend == StorageBackend::CredentialManager {
+        match credential::write_token(token_info) {
+            Ok(()) => {
+                debug!("Token saved to Credential Manager");
+                // Don't leave a copy of the token on disk after switching backends
+                let token_path = get_token_path()?;
+                if token_path.exists() {
+                    fs::remove_file(&token_path)?;
+                    info!(
+                        "Removed {} now that the token is in Credential Manager",
+                        token_path.display()
+                    );
+                }
+                return Ok(());
+            }
+            Err(e) => warn!(
+                "Failed to save token to Credential Manager ({}), saving to file instead",
+                e
+            ),
+        }
+    }
+
     let token_path = get_token_path()?;
     let json = serde_json::to_string_pretty(token_info)?;
-    let mut file = File::create(token_path)?;
-    file.write_all(json.as_bytes())?;
+    write_atomic(&token_path, json.as_bytes())?;
+    debug!("Token saved to file");
     Ok(())
 }
 
-// Function to load client secrets
-pub fn load_client_secrets() -> std::result::Result<ClientSecrets, Box<dyn std::error::Error>> {
-    let secrets_path = get_secrets_path()?;
-    let mut file = File::open(secrets_path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+// Client secrets parsed by the first successful load, reused by later loads (e.g. each hourly
+// token refresh) since secret.json rarely changes
+static CLIENT_SECRETS: Mutex<Option<InstalledSecrets>> = Mutex::new(None);
 
-    let secrets: ClientSecrets = serde_json::from_str(&contents)?;
+// Function to load client secrets, from the cache if they were loaded before
+pub fn load_client_secrets() -> std::result::Result<InstalledSecrets, Box<dyn std::error::Error>> {
+    if let Some(secrets) = CLIENT_SECRETS.lock().ok().and_then(|cached| cached.clone()) {
+        return Ok(secrets);
+    }
+    let secrets = read_client_secrets()?;
+    if let Ok(mut cached) = CLIENT_SECRETS.lock() {
+        *cached = Some(secrets.clone());
+    }
     Ok(secrets)
 }
 
-// Function to perform OAuth flow
-pub async fn oauth_flow() -> std::result::Result<TokenInfo, Box<dyn std::error::Error>> {
-    // Load client secrets
+// Drops the cached client secrets, so the next load reads them again. Also offered in the tray
+// menu, for a secret.json replaced while liveframe is running.
+pub fn forget_client_secrets() {
+    if let Ok(mut cached) = CLIENT_SECRETS.lock() {
+        *cached = None;
+    }
+}
+
+// Reads the client secrets from the LIVEFRAME_SECRET environment variable if set and from
+// secret.json otherwise
+fn read_client_secrets() -> std::result::Result<InstalledSecrets, Box<dyn std::error::Error>> {
+    let contents = match std::env::var(SECRET_ENV_VAR) {
+        Ok(contents) => {
+            static WARN_ONCE: Once = Once::new();
+            WARN_ONCE.call_once(|| {
+                warn!(
+                    "Using client secrets from the {} environment variable. Environment \
+                     variables may be visible to other processes (e.g. in process listings)",
+                    SECRET_ENV_VAR
+                )
+            });
+            contents
+        }
+        Err(_) => {
+            let secrets_path = get_secrets_path()?;
+            let mut file = File::open(secrets_path)?;
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            contents
+        }
+    };
+
+    let secrets: ClientSecrets = serde_json::from_str(&contents)?;
+    match secrets {
+        ClientSecrets {
+            installed: Some(installed),
+            ..
+        } => Ok(installed),
+        ClientSecrets { web: Some(web), .. } => {
+            if !web.redirect_uris.iter().any(|uri| is_localhost_uri(uri)) {
+                return Err(ConfigError::WebCredentialsNotSupported.into());
+            }
+            warn!(
+                "secret.json contains web application credentials. liveframe is meant to be used \
+                 with Desktop app credentials; the OAuth flow only works if a localhost redirect \
+                 URI is registered for the web client"
+            );
+            Ok(web)
+        }
+        _ => Err(ConfigError::MissingCredentials.into()),
+    }
+}
+
+// Checks that the client secrets file exists and parses, so a missing file is reported at
+// startup rather than when the OAuth flow first needs it
+pub fn validate_client_secrets() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    load_client_secrets().map(|_| ()).map_err(|e| {
+        format!(
+            "{}. Please place your OAuth credentials at `~/.liveframe/secret.json`. \
+             See README for instructions.",
+            e
+        )
+        .into()
+    })
+}
+
+// Function to perform OAuth flow. Cancelling `cancel` shuts the callback server down gracefully
+// and fails the flow with a NonRetriableError.
+pub async fn oauth_flow(
+    config: &OAuthConfig,
+    cancel: &CancellationToken,
+) -> std::result::Result<TokenInfo, Box<dyn std::error::Error>> {
+    if cancel.is_cancelled() {
+        return Err(NonRetriableError("OAuth flow cancelled".to_string()).into());
+    }
+
+    // Load client secrets. A new authorization is when secret.json was most likely replaced
+    // (e.g. with credentials of another project), so it is read again.
     info!("Loading client secrets...");
+    forget_client_secrets();
     let secrets = load_client_secrets()?;
+    if config.out_of_band {
+        return out_of_band_flow(config, secrets, cancel).await;
+    }
+    let (redirect_uri, callback_port) = select_redirect_uri(&secrets, config)?;
 
     // Create OAuth client
     debug!("Creating OAuth client...");
-    let client = BasicClient::new(
-        ClientId::new(secrets.installed.client_id),
-        Some(ClientSecret::new(secrets.installed.client_secret)),
-        AuthUrl::new(secrets.installed.auth_uri)?,
-        Some(TokenUrl::new(secrets.installed.token_uri)?),
-    )
-    .set_redirect_uri(RedirectUrl::new("http://localhost:8080".to_string())?);
-
-    // Generate PKCE challenge
-    debug!("Generating PKCE challenge...");
-    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
-
-    // Generate the authorization URL
-    let (auth_url, csrf_state) = client
-        .authorize_url(CsrfToken::new_random)
-        .add_scope(Scope::new(
-            "https://www.googleapis.com/auth/youtube.readonly".to_string(),
-        ))
-        .set_pkce_challenge(pkce_challenge)
-        .url();
+    let client = create_oauth_client(secrets, &redirect_uri)?;
+    let (auth_url, csrf_state, pkce_verifier) = authorization_url(&client, &config.scopes);
 
     info!("Opening authorization URL in browser...");
 
@@ -224,48 +483,70 @@ pub async fn oauth_flow() -> std::result::Result<TokenInfo, Box<dyn std::error::
         csrf_state: csrf_state.secret().clone(),
         pkce_verifier: Some(pkce_verifier),
         auth_code_received_tx: Some(tx),
+        success_redirect_url: Some(config.success_redirect_url.clone())
+            .filter(|url| !url.is_empty()),
     }));
 
-    // Start the HTTP server for the OAuth callback
-    info!("Starting OAuth callback server on http://localhost:8080");
-    let state_clone = state.clone();
-    let make_service = make_service_fn(move |_| {
-        let state = state_clone.clone();
-        async move {
-            Ok::<_, hyper::Error>(service_fn(move |req| {
-                let state = state.clone();
-                async move { handle_oauth_callback(req, state).await }
-            }))
+    // Cancelling signals the same channel as the callback, so the server shuts down gracefully
+    let cancel_state = state.clone();
+    let cancel_signal = cancel.clone();
+    let cancel_task = tokio::spawn(async move {
+        cancel_signal.cancelled().await;
+        if let Some(tx) = lock_oauth_state(&cancel_state).auth_code_received_tx.take() {
+            let _ = tx.send(());
         }
     });
 
-    let addr = ([127, 0, 0, 1], 8080).into();
-    let server = Server::bind(&addr).serve(make_service);
+    // Start the HTTP server for the OAuth callback
+    info!("Starting OAuth callback server on {}", redirect_uri);
+    let addr: SocketAddr = ([127, 0, 0, 1], callback_port).into();
 
-    // Run the server with a timeout
+    // Wait for the auth code to be received or timeout after 2 minutes
     debug!("Waiting for authorization callback (timeout: 2 minutes)...");
-    let server_with_timeout = async move {
-        let server_future = server.with_graceful_shutdown(async {
-            // Wait for the auth code to be received or timeout after 2 minutes
-            tokio::select! {
-                _ = rx => {
+    let shutdown = async {
+        tokio::select! {
+            _ = rx => {
+                if cancel.is_cancelled() {
+                    info!("OAuth flow cancelled, shutting down server");
+                } else {
                     debug!("Authorization code received, shutting down server");
                 }
-                _ = tokio::time::sleep(Duration::from_secs(120)) => {
-                    warn!("Timeout waiting for authorization (2 minutes elapsed)");
-                }
             }
-        });
-
-        server_future.await
+            _ = tokio::time::sleep(Duration::from_secs(120)) => {
+                warn!("Timeout waiting for authorization (2 minutes elapsed)");
+            }
+        }
     };
 
     // Run the server and wait for it to complete
-    server_with_timeout.await?;
+    if config.https_callback {
+        serve_callback_tls(addr, state.clone(), shutdown).await?;
+    } else {
+        let state_clone = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = state_clone.clone();
+            async move {
+                Ok::<_, hyper::Error>(service_fn(move |req| {
+                    let state = state.clone();
+                    async move { handle_oauth_callback(req, state).await }
+                }))
+            }
+        });
+
+        Server::bind(&addr)
+            .serve(make_service)
+            .with_graceful_shutdown(shutdown)
+            .await?;
+    }
+
+    cancel_task.abort();
+    if cancel.is_cancelled() {
+        return Err(NonRetriableError("OAuth flow cancelled".to_string()).into());
+    }
 
     // Get the authorization code from the state
     let auth_code = {
-        let state_guard = state.lock().unwrap();
+        let state_guard = lock_oauth_state(&state);
         state_guard
             .auth_code
             .clone()
@@ -274,20 +555,112 @@ pub async fn oauth_flow() -> std::result::Result<TokenInfo, Box<dyn std::error::
 
     // Get the PKCE verifier from the state
     let pkce_verifier = {
-        let mut state_guard = state.lock().unwrap();
+        let mut state_guard = lock_oauth_state(&state);
         state_guard
             .pkce_verifier
             .take()
             .ok_or("PKCE verifier not found")?
     };
 
+    let token_info = exchange_auth_code(&client, auth_code, pkce_verifier).await?;
+
+    info!("OAuth flow completed successfully");
+    Ok(token_info)
+}
+
+// Authorizes without the callback server, for environments where it can't run: Google shows
+// the code after authorization and the user pastes it into the console
+async fn out_of_band_flow(
+    config: &OAuthConfig,
+    secrets: InstalledSecrets,
+    cancel: &CancellationToken,
+) -> std::result::Result<TokenInfo, Box<dyn std::error::Error>> {
+    let client = create_oauth_client(secrets, OUT_OF_BAND_REDIRECT_URI)?;
+    let (auth_url, _, pkce_verifier) = authorization_url(&client, &config.scopes);
+
+    if let Err(e) = open_url_in_browser(auth_url.as_str()) {
+        warn!("Failed to open URL in browser: {}", e);
+    }
+    println!("Open this URL in your browser to authorize liveframe:");
+    println!("{}", auth_url);
+    print!("Then paste the authorization code here: ");
+    std::io::stdout().flush()?;
+
+    // The blocking read is left behind when cancelled; it ends with the process
+    let read_line = tokio::task::spawn_blocking(|| {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line).map(|_| line)
+    });
+    let line = tokio::select! {
+        line = read_line => line??,
+        _ = cancel.cancelled() => {
+            return Err(NonRetriableError("OAuth flow cancelled".to_string()).into());
+        }
+    };
+    let auth_code = line.trim();
+    if auth_code.is_empty() {
+        return Err(NonRetriableError("No authorization code entered".to_string()).into());
+    }
+
+    let token_info = exchange_auth_code(&client, auth_code.to_string(), pkce_verifier).await?;
+    info!("OAuth flow completed successfully");
+    Ok(token_info)
+}
+
+// Creates the OAuth client for the given secrets and redirect URI
+fn create_oauth_client(
+    secrets: InstalledSecrets,
+    redirect_uri: &str,
+) -> std::result::Result<BasicClient, Box<dyn std::error::Error>> {
+    let client = BasicClient::new(
+        ClientId::new(secrets.client_id),
+        Some(ClientSecret::new(secrets.client_secret)),
+        AuthUrl::new(secrets.auth_uri)?,
+        Some(TokenUrl::new(secrets.token_uri)?),
+    )
+    .set_redirect_uri(RedirectUrl::new(redirect_uri.to_string())?);
+    Ok(client)
+}
+
+// Generates the authorization URL with a fresh CSRF state and PKCE challenge. With
+// include_granted_scopes, the new token also covers the scopes granted before, so adding a
+// scope is an incremental authorization rather than a replacement.
+fn authorization_url(
+    client: &BasicClient,
+    scopes: &[String],
+) -> (url::Url, CsrfToken, PkceCodeVerifier) {
+    debug!("Generating PKCE challenge...");
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+    let (auth_url, csrf_state) = client
+        .authorize_url(CsrfToken::new_random)
+        .add_scopes(scopes.iter().cloned().map(Scope::new))
+        .add_extra_param("include_granted_scopes", "true")
+        .set_pkce_challenge(pkce_challenge)
+        .url();
+    (auth_url, csrf_state, pkce_verifier)
+}
+
+// Exchanges an authorization code for tokens
+async fn exchange_auth_code(
+    client: &BasicClient,
+    auth_code: String,
+    pkce_verifier: PkceCodeVerifier,
+) -> std::result::Result<TokenInfo, Box<dyn std::error::Error>> {
     // Exchange the authorization code for an access token
     info!("Exchanging authorization code for access token...");
     let token_result = client
         .exchange_code(AuthorizationCode::new(auth_code))
         .set_pkce_verifier(pkce_verifier)
         .request_async(oauth2::reqwest::async_http_client)
-        .await?;
+        .await
+        .map_err(|e| -> Box<dyn std::error::Error> {
+            if is_retriable_token_error(&e) {
+                e.into()
+            } else {
+                NonRetriableError(format!("Authorization code rejected: {:?}", e)).into()
+            }
+        })?;
 
     // Create token info
     debug!("Creating token info with expiry time");
@@ -302,12 +675,241 @@ pub async fn oauth_flow() -> std::result::Result<TokenInfo, Box<dyn std::error::
             + chrono::Duration::seconds(
                 token_result.expires_in().unwrap_or_default().as_secs() as i64
             ),
+        scopes: granted_scopes(&token_result),
     };
 
-    info!("OAuth flow completed successfully");
     Ok(token_info)
 }
 
+// Returns the scopes reported in a token response, empty if the response doesn't list them
+fn granted_scopes(token_result: &BasicTokenResponse) -> Vec<String> {
+    token_result
+        .scopes()
+        .map(|scopes| scopes.iter().map(|scope| scope.to_string()).collect())
+        .unwrap_or_default()
+}
+
+// Function to get the path of the authorization started by --print-auth-url
+fn get_pending_auth_path() -> std::result::Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_liveframe_dir()?.join("pending_auth.json"))
+}
+
+// Prints the authorization URL without starting the callback server or opening a browser.
+// The state and PKCE verifier are saved so --complete-auth can finish the authorization.
+pub fn print_auth_url(config: &OAuthConfig) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let secrets = load_client_secrets()?;
+    let redirect_uri = if config.out_of_band {
+        OUT_OF_BAND_REDIRECT_URI.to_string()
+    } else {
+        select_redirect_uri(&secrets, config)?.0
+    };
+    let client = create_oauth_client(secrets, &redirect_uri)?;
+    let (auth_url, csrf_state, pkce_verifier) = authorization_url(&client, &config.scopes);
+
+    let pending = PendingAuth {
+        csrf_state: csrf_state.secret().clone(),
+        pkce_verifier: pkce_verifier.secret().clone(),
+        redirect_uri,
+    };
+    let pending_path = get_pending_auth_path()?;
+    fs::create_dir_all(get_liveframe_dir()?)?;
+    write_atomic(
+        &pending_path,
+        serde_json::to_string_pretty(&pending)?.as_bytes(),
+    )?;
+    debug!("Pending authorization saved to {}", pending_path.display());
+
+    println!("{}", auth_url);
+    Ok(())
+}
+
+// Finishes an authorization started by --print-auth-url. Accepts either the bare code or the
+// whole URL the browser was redirected to, in which case the state is checked as well.
+pub async fn complete_auth(
+    config: &OAuthConfig,
+    code: &str,
+) -> std::result::Result<TokenInfo, Box<dyn std::error::Error>> {
+    let pending_path = get_pending_auth_path()?;
+    let pending: PendingAuth = match fs::read_to_string(&pending_path) {
+        Ok(contents) => serde_json::from_str(&contents)?,
+        Err(_) => return Err("No pending authorization, run with --print-auth-url first".into()),
+    };
+
+    let auth_code = match url::Url::parse(code) {
+        Ok(redirected) => {
+            let params: HashMap<_, _> = redirected.query_pairs().into_owned().collect();
+            if params.get("state") != Some(&pending.csrf_state) {
+                return Err(
+                    "State in the redirect URL doesn't match the pending authorization".into(),
+                );
+            }
+            params
+                .get("code")
+                .cloned()
+                .ok_or("No code parameter in the redirect URL")?
+        }
+        Err(_) => code.to_string(),
+    };
+
+    let secrets = load_client_secrets()?;
+    let client = create_oauth_client(secrets, &pending.redirect_uri)?;
+    let token_info = exchange_auth_code(
+        &client,
+        auth_code,
+        PkceCodeVerifier::new(pending.pkce_verifier),
+    )
+    .await?;
+
+    save_token(config, &token_info)?;
+    fs::remove_file(&pending_path)?;
+    info!("Authorization completed successfully");
+    Ok(token_info)
+}
+
+// Whether the redirect URI points at this machine, where the callback server runs
+fn is_localhost_uri(uri: &str) -> bool {
+    url::Url::parse(uri)
+        .is_ok_and(|parsed| matches!(parsed.host_str(), Some("localhost" | "127.0.0.1")))
+}
+
+// Picks the localhost redirect URI registered in secret.json that the local callback server can
+// serve, returning it with the port to listen on. Desktop app credentials usually register a bare
+// `http://localhost`, which Google accepts with any port, so the default port is added to it.
+fn select_redirect_uri(
+    secrets: &InstalledSecrets,
+    config: &OAuthConfig,
+) -> std::result::Result<(String, u16), Box<dyn std::error::Error>> {
+    let scheme = if config.https_callback {
+        "https"
+    } else {
+        "http"
+    };
+
+    for uri in &secrets.redirect_uris {
+        let parsed = match url::Url::parse(uri) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                debug!("Skipping unparseable redirect URI {}: {}", uri, e);
+                continue;
+            }
+        };
+        let host = match parsed.host_str() {
+            Some(host @ ("localhost" | "127.0.0.1")) => host,
+            _ => continue,
+        };
+        if parsed.scheme() != scheme {
+            continue;
+        }
+
+        let port = explicit_port(uri, &parsed).unwrap_or(DEFAULT_CALLBACK_PORT);
+        let path = match parsed.path() {
+            "/" => "",
+            path => path,
+        };
+        let redirect_uri = format!("{}://{}:{}{}", scheme, host, port, path);
+        debug!(
+            "Using redirect URI {} (registered as {})",
+            redirect_uri, uri
+        );
+        return Ok((redirect_uri, port));
+    }
+
+    Err(format!(
+        "No {}://localhost redirect URI is registered in ~/.liveframe/secret.json (found: {:?}). \
+         Use a Desktop app OAuth client, or register a localhost redirect URI with the provider",
+        scheme, secrets.redirect_uris
+    )
+    .into())
+}
+
+// Returns the port written in a URI. Url::port() leaves out the scheme's default port, so an
+// explicit `:80` is told apart from no port from the raw URI.
+fn explicit_port(uri: &str, parsed: &url::Url) -> Option<u16> {
+    parsed.port().or_else(|| {
+        let authority = uri.split_once("://")?.1.split(['/', '?', '#']).next()?;
+        let (_, port) = authority.rsplit_once(':')?;
+        (!port.is_empty())
+            .then(|| parsed.port_or_known_default())
+            .flatten()
+    })
+}
+
+// Builds a TLS config with a freshly generated self-signed certificate for localhost
+fn self_signed_tls_config() -> std::result::Result<rustls::ServerConfig, Box<dyn std::error::Error>>
+{
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+    let tls_config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![rustls::Certificate(cert.serialize_der()?)],
+            rustls::PrivateKey(cert.serialize_private_key_der()),
+        )?;
+    Ok(tls_config)
+}
+
+// Serves the OAuth callback over TLS until `shutdown` completes
+async fn serve_callback_tls<F>(
+    addr: SocketAddr,
+    state: Arc<Mutex<OAuthState>>,
+    shutdown: F,
+) -> std::result::Result<(), Box<dyn std::error::Error>>
+where
+    F: Future<Output = ()>,
+{
+    let acceptor = TlsAcceptor::from(Arc::new(self_signed_tls_config()?));
+    let listener = TcpListener::bind(addr).await?;
+
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            accepted = listener.accept() => {
+                let (stream, _) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!("Failed to accept callback connection: {}", e);
+                        continue;
+                    }
+                };
+
+                let acceptor = acceptor.clone();
+                let state = state.clone();
+                tokio::spawn(async move {
+                    // Browsers abort the first handshake until the user accepts the certificate
+                    let stream = match acceptor.accept(stream).await {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            debug!("TLS handshake with callback client failed: {}", e);
+                            return;
+                        }
+                    };
+
+                    let service = service_fn(move |req| {
+                        let state = state.clone();
+                        async move { handle_oauth_callback(req, state).await }
+                    });
+                    if let Err(e) = Http::new().serve_connection(stream, service).await {
+                        debug!("Callback connection error: {}", e);
+                    }
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Locks the OAuth state, recovering it if a callback handler panicked while holding the lock,
+// so one failed request doesn't break every later callback of the flow
+fn lock_oauth_state(state: &Mutex<OAuthState>) -> MutexGuard<'_, OAuthState> {
+    state.lock().unwrap_or_else(|poisoned| {
+        warn!("OAuth state lock was poisoned by a panicked callback handler, recovering it");
+        state.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
 // Function to handle OAuth callback
 pub async fn handle_oauth_callback(
     req: Request<Body>,
@@ -325,14 +927,14 @@ pub async fn handle_oauth_callback(
     if let (Some(code), Some(received_state)) = (params.get("code"), params.get("state")) {
         // Verify CSRF state
         let expected_state = {
-            let state_guard = state.lock().unwrap();
+            let state_guard = lock_oauth_state(&state);
             state_guard.csrf_state.clone()
         };
 
         if received_state == &expected_state {
             // Store the authorization code and signal that it's been received
-            {
-                let mut state_guard = state.lock().unwrap();
+            let success_redirect_url = {
+                let mut state_guard = lock_oauth_state(&state);
                 state_guard.auth_code = Some(code.clone());
 
                 // Send signal through the channel if it exists
@@ -340,25 +942,92 @@ pub async fn handle_oauth_callback(
                     let _ = tx.send(());
                     debug!("Sent signal that authorization code was received");
                 }
-            }
+                state_guard.success_redirect_url.clone()
+            };
 
-            *response.body_mut() = Body::from(
-                "Authorization successful! You can close this window and return to the application.",
-            );
+            match success_redirect_url
+                .and_then(|url| hyper::header::HeaderValue::from_str(&url).ok())
+            {
+                Some(location) => {
+                    *response.status_mut() = StatusCode::FOUND;
+                    response
+                        .headers_mut()
+                        .insert(hyper::header::LOCATION, location);
+                }
+                None => {
+                    response = callback_page(
+                        StatusCode::OK,
+                        "Authorization successful",
+                        "liveframe can now check your streaming status.",
+                        "This tab closes in a few seconds. If it doesn't, you can close it and \
+                         return to the application.",
+                    );
+                }
+            }
         } else {
-            *response.status_mut() = StatusCode::BAD_REQUEST;
-            *response.body_mut() = Body::from("Invalid state parameter");
+            response = callback_page(
+                StatusCode::BAD_REQUEST,
+                "Authorization failed",
+                "The response doesn't belong to the current authorization request (invalid \
+                 state parameter).",
+                "Start the authorization again from liveframe, and only use the most recently \
+                 opened browser tab.",
+            );
         }
+    } else if let Some(error) = params.get("error") {
+        warn!("Authorization was not granted: {}", error);
+        let message = format!("Google didn't grant access ({}).", html_escape(error));
+        response = callback_page(
+            StatusCode::BAD_REQUEST,
+            "Authorization denied",
+            &message,
+            "liveframe needs access to your YouTube account to detect live streams. Restart \
+             liveframe to try again.",
+        );
     } else {
-        *response.status_mut() = StatusCode::BAD_REQUEST;
-        *response.body_mut() = Body::from("Missing code or state parameter");
+        response = callback_page(
+            StatusCode::BAD_REQUEST,
+            "Authorization failed",
+            "The response is missing the code or state parameter.",
+            "Start the authorization again from liveframe.",
+        );
     }
 
     Ok(response)
 }
 
+// Renders the callback page. Only the success page closes itself. `message` is inserted as
+// HTML, so it must already be escaped.
+fn callback_page(status: StatusCode, title: &str, message: &str, hint: &str) -> Response<Body> {
+    let success = status.is_success();
+    let page = CALLBACK_PAGE_TEMPLATE
+        .replace("{accent}", if success { "#2e7d32" } else { "#c62828" })
+        .replace("{script}", if success { AUTO_CLOSE_SCRIPT } else { "" })
+        .replace("{title}", title)
+        .replace("{message}", message)
+        .replace("{hint}", hint);
+
+    let mut response = Response::new(Body::from(page));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    response
+}
+
+// Escapes text from the request for inclusion in the callback page
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
 // Function to refresh OAuth token
 pub async fn refresh_token(
+    config: &OAuthConfig,
     refresh_token: &str,
 ) -> std::result::Result<TokenInfo, Box<dyn std::error::Error>> {
     // Load client secrets
@@ -367,10 +1036,10 @@ pub async fn refresh_token(
 
     // Create OAuth client
     let client = BasicClient::new(
-        ClientId::new(secrets.installed.client_id),
-        Some(ClientSecret::new(secrets.installed.client_secret)),
-        AuthUrl::new(secrets.installed.auth_uri)?,
-        Some(TokenUrl::new(secrets.installed.token_uri)?),
+        ClientId::new(secrets.client_id),
+        Some(ClientSecret::new(secrets.client_secret)),
+        AuthUrl::new(secrets.auth_uri)?,
+        Some(TokenUrl::new(secrets.token_uri)?),
     );
 
     // Exchange the refresh token for a new access token with retry logic
@@ -386,10 +1055,10 @@ pub async fn refresh_token(
         {
             Ok(token) => break token,
             Err(e) => {
-                if let oauth2::RequestTokenError::ServerResponse(e) = &e {
-                    if e.error() == &BasicErrorResponseType::InvalidGrant {
-                        return Err("Refresh token expired".into());
-                    }
+                if !is_retriable_token_error(&e) {
+                    return Err(
+                        NonRetriableError(format!("Refresh token rejected: {:?}", e)).into(),
+                    );
                 }
 
                 retry_count += 1;
@@ -400,6 +1069,9 @@ pub async fn refresh_token(
                     )
                     .into());
                 }
+                if !RETRY_BUDGET.try_acquire() {
+                    return Err(RetryBudgetExhausted("token refresh".to_string()).into());
+                }
                 warn!(
                     "Error during token refresh (attempt {}/{}): {:?}",
                     retry_count, MAX_RETRIES, e
@@ -420,12 +1092,154 @@ pub async fn refresh_token(
             + chrono::Duration::seconds(
                 token_result.expires_in().unwrap_or_default().as_secs() as i64
             ),
+        scopes: granted_scopes(&token_result),
     };
 
     // Save the new token
     debug!("Saving refreshed token to file...");
-    save_token(&token_info)?;
+    save_token(config, &token_info)?;
     info!("Token refreshed successfully");
 
     Ok(token_info)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+
+    // A path in the temp directory, unique to the test and the test process
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("liveframe-{}-{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn truncated_token_file_falls_back_to_new_auth() {
+        let path = temp_path("truncated-token");
+        fs::write(
+            &path,
+            r#"{"access_token": "ya29.a0Af", "refresh_token": "1//0g"#,
+        )
+        .unwrap();
+
+        let token = load_token_file(&path).unwrap();
+
+        assert!(
+            token.is_none(),
+            "a truncated token should start a new auth flow"
+        );
+        assert!(!path.exists(), "the truncated token file should be deleted");
+    }
+
+    #[test]
+    fn empty_token_file_falls_back_to_new_auth() {
+        let path = temp_path("empty-token");
+        fs::write(&path, "").unwrap();
+
+        assert!(load_token_file(&path).unwrap().is_none());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn poisoned_oauth_state_is_recovered() {
+        let state = Arc::new(Mutex::new(OAuthState {
+            auth_code: None,
+            csrf_state: "csrf-state".to_string(),
+            pkce_verifier: None,
+            auth_code_received_tx: None,
+            success_redirect_url: None,
+        }));
+
+        // A callback handler that panics while holding the lock, after changing the state
+        let handler_state = state.clone();
+        let handler = std::thread::spawn(move || {
+            let mut state = handler_state.lock().unwrap();
+            state.auth_code = Some("auth-code".to_string());
+            panic!("callback handler panicked");
+        });
+        assert!(handler.join().is_err());
+        assert!(state.is_poisoned());
+
+        let recovered = lock_oauth_state(&state);
+        assert_eq!(recovered.csrf_state, "csrf-state");
+        assert_eq!(recovered.auth_code.as_deref(), Some("auth-code"));
+        drop(recovered);
+        assert!(!state.is_poisoned(), "the poison should be cleared");
+        assert!(state.lock().is_ok());
+    }
+
+    #[test]
+    fn complete_token_file_is_loaded() {
+        let path = temp_path("complete-token");
+        fs::write(
+            &path,
+            r#"{"access_token": "ya29.a0Af", "refresh_token": "1//0g", "expiry": "2030-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+
+        let token = load_token_file(&path).unwrap().expect("token should load");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(token.access_token, "ya29.a0Af");
+        assert_eq!(token.refresh_token, "1//0g");
+        assert!(token.scopes.is_empty());
+    }
+
+    fn secrets_with_redirect_uris(redirect_uris: &[&str]) -> InstalledSecrets {
+        InstalledSecrets {
+            client_id: "client-id".to_string(),
+            client_secret: "client-secret".to_string(),
+            auth_uri: "https://accounts.google.com/o/oauth2/auth".to_string(),
+            token_uri: "https://oauth2.googleapis.com/token".to_string(),
+            redirect_uris: redirect_uris.iter().map(|uri| uri.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn redirect_uri_port_defaults_only_when_not_registered() {
+        let config = OAuthConfig::default();
+        let bare = secrets_with_redirect_uris(&["http://localhost"]);
+        assert_eq!(
+            select_redirect_uri(&bare, &config).unwrap(),
+            (
+                format!("http://localhost:{}", DEFAULT_CALLBACK_PORT),
+                DEFAULT_CALLBACK_PORT
+            )
+        );
+
+        let port_80 = secrets_with_redirect_uris(&["http://localhost:80/callback"]);
+        assert_eq!(
+            select_redirect_uri(&port_80, &config).unwrap(),
+            ("http://localhost:80/callback".to_string(), 80)
+        );
+
+        let other_port = secrets_with_redirect_uris(&["http://127.0.0.1:9000"]);
+        assert_eq!(
+            select_redirect_uri(&other_port, &config).unwrap(),
+            ("http://127.0.0.1:9000".to_string(), 9000)
+        );
+    }
+
+    #[test]
+    fn token_is_refreshed_within_the_expiry_margin() {
+        let clock = FakeClock::new();
+        let config = OAuthConfig {
+            expiry_margin_secs: 60,
+            ..Default::default()
+        };
+        let token = TokenInfo {
+            access_token: "ya29.a0Af".to_string(),
+            refresh_token: "1//0g".to_string(),
+            expiry: clock.now() + chrono::Duration::seconds(120),
+            scopes: Vec::new(),
+        };
+
+        assert!(!token_needs_refresh(&config, &token, &clock));
+        clock.advance(Duration::from_secs(59));
+        assert!(!token_needs_refresh(&config, &token, &clock));
+        clock.advance(Duration::from_secs(1));
+        assert!(token_needs_refresh(&config, &token, &clock));
+        clock.advance(Duration::from_secs(120));
+        assert!(token_needs_refresh(&config, &token, &clock));
+    }
+}
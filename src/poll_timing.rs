@@ -0,0 +1,57 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use log::{debug, warn};
+
+// Number of recent poll cycles the median is computed over
+const POLL_WINDOW: usize = 100;
+// Polls taking longer than this multiple of the median are logged as warnings
+const SLOW_POLL_FACTOR: u32 = 3;
+// Polls needed before the median is trusted, so the first few polls don't warn
+const MIN_SAMPLES: usize = 10;
+
+// Rolling record of how long each poll cycle took (token refresh, API calls and window
+// updates, excluding the sleep), to spot jitter delaying status updates
+pub struct PollTimings {
+    recent: VecDeque<Duration>,
+}
+
+impl PollTimings {
+    pub fn new() -> Self {
+        Self {
+            recent: VecDeque::with_capacity(POLL_WINDOW),
+        }
+    }
+
+    // Records the duration of a poll cycle, warning if it was much slower than usual
+    pub fn record(&mut self, elapsed: Duration) {
+        let median = self.median();
+        if self.recent.len() == POLL_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(elapsed);
+
+        debug!("Poll cycle took {}ms", elapsed.as_millis());
+        if let Some(median) = median {
+            if elapsed > median * SLOW_POLL_FACTOR {
+                warn!(
+                    "Poll cycle took {}ms, over {} times the median of the last {} polls ({}ms)",
+                    elapsed.as_millis(),
+                    SLOW_POLL_FACTOR,
+                    self.recent.len() - 1,
+                    median.as_millis()
+                );
+            }
+        }
+    }
+
+    // Median of the recorded durations, or None while there are too few of them
+    fn median(&self) -> Option<Duration> {
+        if self.recent.len() < MIN_SAMPLES {
+            return None;
+        }
+        let mut sorted: Vec<_> = self.recent.iter().copied().collect();
+        sorted.sort_unstable();
+        Some(sorted[sorted.len() / 2])
+    }
+}
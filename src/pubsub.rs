@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use hmac::{Hmac, Mac};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::{debug, error, info, warn};
+use rand::distributions::{Alphanumeric, DistString};
+use regex::Regex;
+use sha1::Sha1;
+use tokio::sync::Notify;
+
+use crate::config::PubSubConfig;
+
+const HUB_URL: &str = "https://pubsubhubbub.appspot.com/subscribe";
+
+// Regular polling resumes once notifications haven't been deliverable for this long
+const FALLBACK_AFTER: Duration = Duration::from_secs(5 * 60);
+
+// Time between probes of the callback URL. A channel can go hours without a notification, so
+// the probes show whether one could still be delivered (e.g. the tunnel is up).
+const PROBE_INTERVAL: Duration = Duration::from_secs(60);
+
+// Query parameter of the probe requests
+const PROBE_PARAM: &str = "liveframe.probe";
+
+// Delay before retrying a failed subscription request
+const SUBSCRIBE_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+// Subscription to the channel's YouTube feed on the PubSubHubbub hub. The hub notifies the
+// callback whenever a video of the channel is published or updated, including when a
+// broadcast goes live or ends.
+pub struct PubSub {
+    config: PubSubConfig,
+    topic: String,
+    // Shared secret the hub signs notifications with
+    secret: String,
+    // Token in the probe requests, so other requests can't pass for them
+    probe_token: String,
+    // When a request last arrived through the callback URL (a notification, a verification or a
+    // probe), showing that notifications can be delivered
+    last_delivery: Mutex<Instant>,
+    // End of the current lease once the hub has verified the subscription, the time given for
+    // the verification before that
+    lease_until: Mutex<Instant>,
+    notified: Notify,
+}
+
+impl PubSub {
+    // Starts the notification receiver and keeps the subscription renewed in the background
+    pub fn start(
+        config: &PubSubConfig,
+    ) -> std::result::Result<Arc<Self>, Box<dyn std::error::Error>> {
+        if config.channel_id.is_empty() || config.callback_url.is_empty() {
+            return Err("liveframe watch needs channel_id and callback_url under [pubsub]".into());
+        }
+
+        let pubsub = Arc::new(Self {
+            config: config.clone(),
+            topic: format!(
+                "https://www.youtube.com/xml/feeds/videos.xml?channel_id={}",
+                config.channel_id
+            ),
+            secret: if config.secret.is_empty() {
+                Alphanumeric.sample_string(&mut rand::thread_rng(), 32)
+            } else {
+                config.secret.clone()
+            },
+            probe_token: Alphanumeric.sample_string(&mut rand::thread_rng(), 32),
+            last_delivery: Mutex::new(Instant::now()),
+            lease_until: Mutex::new(Instant::now() + FALLBACK_AFTER),
+            notified: Notify::new(),
+        });
+
+        let addr = SocketAddr::from(([0, 0, 0, 0], config.listen_port));
+        let server_pubsub = pubsub.clone();
+        let make_service = make_service_fn(move |_| {
+            let pubsub = server_pubsub.clone();
+            async move {
+                Ok::<_, hyper::Error>(service_fn(move |req| {
+                    let pubsub = pubsub.clone();
+                    async move { pubsub.handle_request(req).await }
+                }))
+            }
+        });
+        let server = Server::try_bind(&addr)?.serve(make_service);
+        info!("Listening for feed notifications on {}", addr);
+        tokio::spawn(async move {
+            if let Err(e) = server.await {
+                error!("Feed notification receiver stopped: {}", e);
+            }
+        });
+
+        tokio::spawn(pubsub.clone().keep_subscribed());
+        tokio::spawn(pubsub.clone().keep_probing());
+        Ok(pubsub)
+    }
+
+    // Completes when the hub delivers a notification
+    pub async fn notified(&self) {
+        self.notified.notified().await
+    }
+
+    // Whether notifications have been undeliverable for long enough that regular polling
+    // should take over
+    pub fn fallback_polling(&self) -> bool {
+        let now = Instant::now();
+        let delivery_stale = match self.last_delivery.lock() {
+            Ok(last_delivery) => now.saturating_duration_since(*last_delivery) > FALLBACK_AFTER,
+            Err(_) => true,
+        };
+        let lease_expired = match self.lease_until.lock() {
+            Ok(until) => now > *until,
+            Err(_) => true,
+        };
+        delivery_stale || lease_expired
+    }
+
+    // Records that a request arrived through the callback URL
+    fn delivered(&self) {
+        if let Ok(mut last_delivery) = self.last_delivery.lock() {
+            *last_delivery = Instant::now();
+        }
+    }
+
+    // Sends a request to the callback URL every PROBE_INTERVAL. The receiver records it as a
+    // delivery if it arrives.
+    async fn keep_probing(self: Arc<Self>) {
+        let client = reqwest::Client::new();
+        let mut probe_url = match url::Url::parse(&self.config.callback_url) {
+            Ok(url) => url,
+            Err(e) => {
+                warn!("Not probing the callback URL, which is invalid: {}", e);
+                return;
+            }
+        };
+        probe_url
+            .query_pairs_mut()
+            .append_pair(PROBE_PARAM, &self.probe_token);
+        loop {
+            tokio::time::sleep(PROBE_INTERVAL).await;
+            if let Err(e) = client.get(probe_url.clone()).send().await {
+                debug!("Callback URL probe failed: {}", e);
+            }
+        }
+    }
+
+    // Sends the subscription request, renewing it halfway through each lease
+    async fn keep_subscribed(self: Arc<Self>) {
+        let client = reqwest::Client::new();
+        loop {
+            let delay = match self.subscribe(&client).await {
+                Ok(()) => {
+                    debug!("Subscription requested, waiting for the hub to verify it");
+                    Duration::from_secs(self.config.lease_secs / 2)
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to subscribe to the channel feed ({}), retrying in {}s",
+                        e,
+                        SUBSCRIBE_RETRY_DELAY.as_secs()
+                    );
+                    SUBSCRIBE_RETRY_DELAY
+                }
+            };
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn subscribe(
+        &self,
+        client: &reqwest::Client,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let lease_secs = self.config.lease_secs.to_string();
+        let response = client
+            .post(HUB_URL)
+            .form(&[
+                ("hub.mode", "subscribe"),
+                ("hub.topic", self.topic.as_str()),
+                ("hub.callback", self.config.callback_url.as_str()),
+                ("hub.verify", "async"),
+                ("hub.lease_seconds", lease_secs.as_str()),
+                ("hub.secret", self.secret.as_str()),
+            ])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            return Err(format!("hub returned {}: {}", status, response.text().await?).into());
+        }
+        Ok(())
+    }
+
+    // Answers the hub's verification requests (GET) and takes notifications (POST).
+    // Notifications only trigger a status check, and ones not signed with the secret are ignored.
+    async fn handle_request(
+        &self,
+        req: Request<Body>,
+    ) -> std::result::Result<Response<Body>, hyper::Error> {
+        match *req.method() {
+            Method::GET => {
+                let params: HashMap<_, _> =
+                    url::form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes())
+                        .into_owned()
+                        .collect();
+                if let Some(token) = params.get(PROBE_PARAM) {
+                    let mut response = Response::new(Body::empty());
+                    if *token == self.probe_token {
+                        self.delivered();
+                    } else {
+                        *response.status_mut() = StatusCode::NOT_FOUND;
+                    }
+                    return Ok(response);
+                }
+                Ok(self.verify(&params))
+            }
+            Method::POST => {
+                let signature = req
+                    .headers()
+                    .get("X-Hub-Signature")
+                    .and_then(|signature| signature.to_str().ok())
+                    .map(str::to_string);
+                let body = hyper::body::to_bytes(req.into_body()).await?;
+                if !signature_matches(&self.secret, &body, signature.as_deref()) {
+                    // The hub expects a success response even for notifications it didn't sign
+                    warn!("Ignoring a feed notification without a valid signature");
+                    return Ok(Response::new(Body::empty()));
+                }
+                self.delivered();
+                let feed = String::from_utf8_lossy(&body);
+                match video_id(&feed) {
+                    Some(video_id) => info!("Feed notification for video {}", video_id),
+                    None => info!("Feed notification received"),
+                }
+                self.notified.notify_one();
+                Ok(Response::new(Body::empty()))
+            }
+            _ => {
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+                Ok(response)
+            }
+        }
+    }
+
+    fn verify(&self, params: &HashMap<String, String>) -> Response<Body> {
+        let mode = params.get("hub.mode").map(String::as_str);
+        let topic = params.get("hub.topic").map(String::as_str);
+        let mut response = Response::new(Body::empty());
+
+        if topic != Some(self.topic.as_str()) {
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            return response;
+        }
+        match (mode, params.get("hub.challenge")) {
+            (Some("subscribe"), Some(challenge)) => {
+                let lease = params
+                    .get("hub.lease_seconds")
+                    .and_then(|lease| lease.parse().ok())
+                    .unwrap_or(self.config.lease_secs);
+                info!("Channel feed subscription verified for {}s", lease);
+                if let Ok(mut until) = self.lease_until.lock() {
+                    *until = Instant::now() + Duration::from_secs(lease);
+                }
+                self.delivered();
+                *response.body_mut() = Body::from(challenge.clone());
+            }
+            (Some("denied"), _) => {
+                warn!(
+                    "The hub denied the channel feed subscription: {}",
+                    params.get("hub.reason").map_or("no reason given", |r| r)
+                );
+            }
+            _ => *response.status_mut() = StatusCode::NOT_FOUND,
+        }
+        response
+    }
+}
+
+// Whether an X-Hub-Signature header (`sha1=<hex HMAC of the body>`) was made with the secret
+fn signature_matches(secret: &str, body: &[u8], signature: Option<&str>) -> bool {
+    let Some(signature) = signature.and_then(|signature| signature.strip_prefix("sha1=")) else {
+        return false;
+    };
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    // Compares in constant time
+    mac.verify_slice(&signature).is_ok()
+}
+
+// Extracts the video ID from an Atom feed notification
+fn video_id(feed: &str) -> Option<String> {
+    let re = Regex::new(r"<yt:videoId>([^<]+)</yt:videoId>").ok()?;
+    re.captures(feed).map(|captures| captures[1].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // X-Hub-Signature of "<feed/>" with the secret "hub-secret"
+    const SIGNATURE: &str = "sha1=d07260cad34b4772d7f4167f7e45b4d5f26fe2a1";
+
+    #[test]
+    fn signed_notification_is_accepted() {
+        assert!(signature_matches("hub-secret", b"<feed/>", Some(SIGNATURE)));
+    }
+
+    #[test]
+    fn forged_or_unsigned_notification_is_rejected() {
+        assert!(!signature_matches(
+            "other-secret",
+            b"<feed/>",
+            Some(SIGNATURE)
+        ));
+        assert!(!signature_matches(
+            "hub-secret",
+            b"<feed></feed>",
+            Some(SIGNATURE)
+        ));
+        assert!(!signature_matches(
+            "hub-secret",
+            b"<feed/>",
+            Some("sha1=zz")
+        ));
+        assert!(!signature_matches("hub-secret", b"<feed/>", None));
+    }
+}
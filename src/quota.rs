@@ -0,0 +1,73 @@
+use chrono::{NaiveDate, Utc};
+use chrono_tz::US::Pacific;
+use log::{info, warn};
+
+// Rotates through the configured YouTube API keys, moving on to the next one when a key runs
+// out of quota. The rotation is kept in memory only and starts over from the first key once
+// the daily quota resets at midnight Pacific time.
+pub struct QuotaKeyPool {
+    keys: Vec<String>,
+    current: usize,
+    // Number of keys found out of quota since the last reset
+    exhausted: usize,
+    // Pacific date the keys were last found out of quota, so the rotation is reset the next day
+    exhausted_on: Option<NaiveDate>,
+}
+
+impl QuotaKeyPool {
+    pub fn new(keys: Vec<String>) -> Self {
+        Self {
+            keys,
+            current: 0,
+            exhausted: 0,
+            exhausted_on: None,
+        }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    // Returns the key to send with the next request, if any keys are configured
+    pub fn current_key(&mut self) -> Option<&str> {
+        if let Some(exhausted_on) = self.exhausted_on {
+            if pacific_today() != exhausted_on {
+                info!("YouTube API quota has reset, starting over from the first API key");
+                self.current = 0;
+                self.exhausted = 0;
+                self.exhausted_on = None;
+            }
+        }
+        self.keys.get(self.current).map(String::as_str)
+    }
+
+    // Marks the current key as out of quota and switches to the next one.
+    // Returns false when every key has run out of quota today.
+    pub fn rotate(&mut self) -> bool {
+        if self.keys.is_empty() {
+            return false;
+        }
+        self.exhausted_on = Some(pacific_today());
+        self.exhausted = (self.exhausted + 1).min(self.keys.len());
+        if self.exhausted == self.keys.len() {
+            warn!(
+                "All {} YouTube API keys are out of quota until midnight Pacific time",
+                self.keys.len()
+            );
+            return false;
+        }
+        let previous = self.current;
+        self.current = (self.current + 1) % self.keys.len();
+        info!(
+            "YouTube API key #{} is out of quota, switching to key #{}",
+            previous + 1,
+            self.current + 1
+        );
+        true
+    }
+}
+
+// The YouTube API quota resets at midnight Pacific time
+fn pacific_today() -> NaiveDate {
+    Utc::now().with_timezone(&Pacific).date_naive()
+}
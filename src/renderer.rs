@@ -0,0 +1,215 @@
+use windows::Win32::Foundation::{COLORREF, RECT};
+use windows::Win32::Graphics::Gdi::{
+    CombineRgn, CreateRectRgn, CreateRectRgnIndirect, CreateRoundRectRgn, CreateSolidBrush,
+    DeleteObject, FillRect, GetStockObject, RoundRect, SelectObject, BLACK_BRUSH, HDC, HRGN,
+    NULL_PEN, RGN_DIFF, RGN_OR,
+};
+
+use crate::config::{FrameConfig, FrameEdge, FrameStyle};
+
+// Draws one frame style. Shapes are made of rectangles, which the window region and the shared
+// memory mirror are built from too, so a new style only has to provide them.
+pub trait FrameRenderer {
+    // Rectangles covered by the frame within the client area
+    fn rects(&self, rect: &RECT, config: &FrameConfig) -> Vec<RECT>;
+
+    // Paints the frame in the given color
+    unsafe fn paint(&self, hdc: HDC, rect: &RECT, config: &FrameConfig, color: COLORREF) {
+        fill_rects(hdc, &self.rects(rect, config), color);
+    }
+
+    // Region the window is clipped to, covering everything paint draws. The caller owns it.
+    unsafe fn region(&self, rect: &RECT, config: &FrameConfig) -> HRGN {
+        rects_region(&self.rects(rect, config))
+    }
+}
+
+unsafe fn fill_rects(hdc: HDC, rects: &[RECT], color: COLORREF) {
+    let brush = CreateSolidBrush(color);
+    for rect in rects {
+        FillRect(hdc, rect, brush);
+    }
+    DeleteObject(brush);
+}
+
+unsafe fn rects_region(rects: &[RECT]) -> HRGN {
+    let region = CreateRectRgn(0, 0, 0, 0);
+    for rect in rects {
+        let rect_region = CreateRectRgnIndirect(rect);
+        CombineRgn(region, region, rect_region, RGN_OR);
+        DeleteObject(rect_region);
+    }
+    region
+}
+
+// Returns the renderer for the configured frame style
+pub fn renderer_for(style: &FrameStyle) -> &'static dyn FrameRenderer {
+    match style {
+        FrameStyle::Full => &RectangularFrameRenderer,
+        FrameStyle::CornerBrackets { .. } => &CornerBracketRenderer,
+        FrameStyle::CornerBadge { .. } => &BadgeRenderer,
+    }
+}
+
+// Strips along the configured edges of the screen. With a border radius and all four edges,
+// the frame is a rounded rectangle instead.
+pub struct RectangularFrameRenderer;
+
+// Returns the corner ellipse sizes (outer, inner) of the rounded frame, or None for a
+// rectangular one. The inner corners are concentric with the outer ones.
+fn rounded_corners(config: &FrameConfig) -> Option<(i32, i32)> {
+    let all_edges = [
+        FrameEdge::Top,
+        FrameEdge::Bottom,
+        FrameEdge::Left,
+        FrameEdge::Right,
+    ]
+    .iter()
+    .all(|edge| config.edges.contains(edge));
+    if config.border_radius == 0 || !all_edges {
+        return None;
+    }
+    let outer = config.border_radius as i32 * 2;
+    Some((outer, (outer - config.thickness as i32 * 2).max(0)))
+}
+
+impl FrameRenderer for RectangularFrameRenderer {
+    // The rounded frame is painted as the outer rounded rectangle in the frame color with the
+    // inner one in black on top, which the color key makes transparent
+    unsafe fn paint(&self, hdc: HDC, rect: &RECT, config: &FrameConfig, color: COLORREF) {
+        let Some((outer, inner)) = rounded_corners(config) else {
+            fill_rects(hdc, &self.rects(rect, config), color);
+            return;
+        };
+        let thickness = config.thickness as i32;
+
+        let brush = CreateSolidBrush(color);
+        // Without a pen RoundRect only fills, leaving out the right and bottom edges
+        let previous_pen = SelectObject(hdc, GetStockObject(NULL_PEN));
+        let previous_brush = SelectObject(hdc, brush);
+        RoundRect(hdc, 0, 0, rect.right + 1, rect.bottom + 1, outer, outer);
+        SelectObject(hdc, GetStockObject(BLACK_BRUSH));
+        RoundRect(
+            hdc,
+            thickness,
+            thickness,
+            rect.right - thickness + 1,
+            rect.bottom - thickness + 1,
+            inner,
+            inner,
+        );
+        SelectObject(hdc, previous_brush);
+        SelectObject(hdc, previous_pen);
+        DeleteObject(brush);
+    }
+
+    unsafe fn region(&self, rect: &RECT, config: &FrameConfig) -> HRGN {
+        let Some((outer, inner)) = rounded_corners(config) else {
+            return rects_region(&self.rects(rect, config));
+        };
+        let thickness = config.thickness as i32;
+
+        let region = CreateRoundRectRgn(0, 0, rect.right + 1, rect.bottom + 1, outer, outer);
+        let inside = CreateRoundRectRgn(
+            thickness,
+            thickness,
+            rect.right - thickness + 1,
+            rect.bottom - thickness + 1,
+            inner,
+            inner,
+        );
+        CombineRgn(region, region, inside, RGN_DIFF);
+        DeleteObject(inside);
+        region
+    }
+
+    fn rects(&self, rect: &RECT, config: &FrameConfig) -> Vec<RECT> {
+        let frame_thickness = config.thickness as i32;
+
+        config
+            .edges
+            .iter()
+            .map(|edge| match edge {
+                FrameEdge::Top => RECT {
+                    left: 0,
+                    top: 0,
+                    right: rect.right,
+                    bottom: frame_thickness,
+                },
+                FrameEdge::Bottom => RECT {
+                    left: 0,
+                    top: rect.bottom - frame_thickness,
+                    right: rect.right,
+                    bottom: rect.bottom,
+                },
+                FrameEdge::Left => RECT {
+                    left: 0,
+                    top: 0,
+                    right: frame_thickness,
+                    bottom: rect.bottom,
+                },
+                FrameEdge::Right => RECT {
+                    left: rect.right - frame_thickness,
+                    top: 0,
+                    right: rect.right,
+                    bottom: rect.bottom,
+                },
+            })
+            .collect()
+    }
+}
+
+// L-shaped brackets in the four corners of the screen, with arms `length` pixels long
+pub struct CornerBracketRenderer;
+
+impl FrameRenderer for CornerBracketRenderer {
+    fn rects(&self, rect: &RECT, config: &FrameConfig) -> Vec<RECT> {
+        let FrameStyle::CornerBrackets { length } = config.style else {
+            return Vec::new();
+        };
+        let thickness = config.thickness as i32;
+        let length = (length as i32).min(rect.right / 2).min(rect.bottom / 2);
+
+        let mut rects = Vec::with_capacity(8);
+        for (right_side, bottom_side) in
+            [(false, false), (true, false), (false, true), (true, true)]
+        {
+            let left = if right_side { rect.right - length } else { 0 };
+            let top = if bottom_side { rect.bottom - length } else { 0 };
+            // The horizontal arm along the top or bottom edge
+            let arm_top = if bottom_side {
+                rect.bottom - thickness
+            } else {
+                0
+            };
+            rects.push(RECT {
+                left,
+                top: arm_top,
+                right: left + length,
+                bottom: arm_top + thickness,
+            });
+            // The vertical arm along the left or right edge
+            let arm_left = if right_side {
+                rect.right - thickness
+            } else {
+                0
+            };
+            rects.push(RECT {
+                left: arm_left,
+                top,
+                right: arm_left + thickness,
+                bottom: top + length,
+            });
+        }
+        rects
+    }
+}
+
+// A corner badge fills its whole (small) window
+pub struct BadgeRenderer;
+
+impl FrameRenderer for BadgeRenderer {
+    fn rects(&self, rect: &RECT, _config: &FrameConfig) -> Vec<RECT> {
+        vec![*rect]
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use log::{info, warn};
+
+// Number of retries allowed per minute across all operations
+const RETRIES_PER_MINUTE: u32 = 10;
+// Time between refills of the retry budget
+const REFILL_INTERVAL: Duration = Duration::from_secs(60);
+
+// Budget shared by every retry loop, so several failing operations can't together hammer the
+// APIs with retries
+pub static RETRY_BUDGET: LazyLock<RetryBudget> =
+    LazyLock::new(|| RetryBudget::new(RETRIES_PER_MINUTE));
+
+// Limits the number of retries in a time window. Each retry takes a token from the budget,
+// and the budget is refilled to its capacity once per minute.
+pub struct RetryBudget {
+    tokens: Arc<AtomicU32>,
+    capacity: u32,
+}
+
+impl RetryBudget {
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            tokens: Arc::new(AtomicU32::new(capacity)),
+            capacity,
+        }
+    }
+
+    // Takes one retry from the budget. Returns false if the budget is exhausted.
+    pub fn try_acquire(&self) -> bool {
+        let acquired = self
+            .tokens
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |tokens| {
+                tokens.checked_sub(1)
+            })
+            .is_ok();
+        if !acquired {
+            warn!("Retry budget exhausted, not retrying until it is refilled");
+        }
+        acquired
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.tokens.load(Ordering::Acquire) == 0
+    }
+
+    // Starts a task refilling the budget once per minute
+    pub fn spawn_refill(&self) {
+        let tokens = self.tokens.clone();
+        let capacity = self.capacity;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(REFILL_INTERVAL);
+            // The first tick completes immediately
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if tokens.swap(capacity, Ordering::AcqRel) == 0 {
+                    info!("Retry budget refilled");
+                }
+            }
+        });
+    }
+}
+
+// A retry was needed but the retry budget is exhausted
+#[derive(Debug)]
+pub struct RetryBudgetExhausted(pub String);
+
+impl std::fmt::Display for RetryBudgetExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Retry budget exhausted, giving up on {}", self.0)
+    }
+}
+
+impl std::error::Error for RetryBudgetExhausted {}
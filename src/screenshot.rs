@@ -0,0 +1,80 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::Local;
+use image::{ImageFormat, RgbaImage};
+use log::info;
+use windows::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_CXSCREEN, SM_CYSCREEN};
+
+use crate::config::Config;
+use crate::window::{self, WindowCommand, WindowRole};
+
+// Time the overlay gets to paint before the screen is captured
+const RENDER_DELAY: Duration = Duration::from_millis(100);
+
+// Shows the overlay in the idle color, captures the main display into a timestamped PNG in
+// `output` (a directory, or the file path) or the current directory, and returns its path.
+// With `keep_visible`, the overlay stays on screen until Ctrl+C.
+pub async fn take_screenshot(
+    config: &Config,
+    output: Option<&Path>,
+    keep_visible: bool,
+) -> std::result::Result<PathBuf, Box<dyn std::error::Error>> {
+    let file_name = format!(
+        "liveframe-screenshot-{}.png",
+        Local::now().format("%Y%m%d-%H%M%S")
+    );
+    let path = match output {
+        Some(output) if output.is_dir() => output.join(file_name),
+        Some(output) => output.to_path_buf(),
+        None => PathBuf::from(file_name),
+    };
+
+    let (window, window_thread) =
+        window::spawn_frame_window(config.frame.clone(), WindowRole::Main)?;
+    window::set_palette(&window, &config.frame_colors_at(Local::now()));
+    window::set_color_state(&window, window::COLOR_IDLE);
+    window.send(WindowCommand::Show);
+    // Wait out the fade into the idle color too, so the capture shows the final color
+    tokio::time::sleep(RENDER_DELAY + Duration::from_millis(config.frame.state_transition_ms))
+        .await;
+
+    let capture = unsafe { capture_screen() };
+    if keep_visible {
+        info!("Keeping the overlay visible, press Ctrl+C to exit");
+    } else {
+        window.send(WindowCommand::Hide);
+    }
+    let (width, height, pixels) = capture?;
+    RgbaImage::from_raw(width, height, pixels)
+        .ok_or("captured pixels don't match the screen size")?
+        .save_with_format(&path, ImageFormat::Png)?;
+    info!(
+        "Saved a {}x{} screenshot to {}",
+        width,
+        height,
+        path.display()
+    );
+
+    if keep_visible {
+        tokio::signal::ctrl_c().await?;
+    }
+    window.send(WindowCommand::Exit);
+    let _ = window_thread.join();
+    Ok(path)
+}
+
+// Copies the main display, including layered windows like the overlay, and returns its size
+// and RGBA pixels, top row first
+unsafe fn capture_screen() -> windows::core::Result<(u32, u32, Vec<u8>)> {
+    let width = GetSystemMetrics(SM_CXSCREEN);
+    let height = GetSystemMetrics(SM_CYSCREEN);
+    let mut pixels = window::capture_screen(width, height)?;
+
+    // GDI returns BGRX; the image crate wants opaque RGBA
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+        pixel[3] = 0xFF;
+    }
+    Ok((width as u32, height as u32, pixels))
+}
@@ -0,0 +1,56 @@
+use std::path::Path;
+
+use log::{debug, warn};
+use windows::core::PCWSTR;
+use windows::Win32::Media::Audio::{PlaySoundW, SND_ASYNC, SND_FILENAME, SND_NODEFAULT};
+
+use crate::config::SoundConfig;
+
+// Streaming status changes a sound cue can be played for
+#[derive(Clone, Copy, Debug)]
+pub enum Cue {
+    StreamStarted,
+    StreamEnded,
+}
+
+// Plays the WAV file configured for the cue, if sound cues are enabled. A missing or unplayable
+// file is logged as a warning.
+pub fn play_cue(config: &SoundConfig, cue: Cue) {
+    if !config.enabled {
+        return;
+    }
+    let path = match cue {
+        Cue::StreamStarted => &config.start_sound,
+        Cue::StreamEnded => &config.stop_sound,
+    };
+    match path {
+        Some(path) => play_wav(path),
+        None => debug!("No sound configured for {:?}", cue),
+    }
+}
+
+fn play_wav(path: &Path) {
+    if !path.is_file() {
+        warn!("Sound file {} not found, not playing it", path.display());
+        return;
+    }
+
+    let path_wide: Vec<u16> = path
+        .as_os_str()
+        .to_string_lossy()
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    // Asynchronously, so the main loop doesn't wait for the sound to end. Without SND_NODEFAULT
+    // an unplayable file would play the system default sound instead.
+    let played = unsafe {
+        PlaySoundW(
+            PCWSTR::from_raw(path_wide.as_ptr()),
+            None,
+            SND_FILENAME | SND_ASYNC | SND_NODEFAULT,
+        )
+    };
+    if !played.as_bool() {
+        warn!("Failed to play {} (is it a WAV file?)", path.display());
+    }
+}
@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{get_liveframe_dir, write_atomic};
+
+// Streaming time accumulated across sessions, kept in ~/.liveframe/stats.json
+#[derive(Serialize, Deserialize, Default)]
+pub struct Stats {
+    pub total_streamed_secs: u64,
+    pub sessions: u64,
+    pub last_session_end: Option<DateTime<Utc>>,
+    // Broadcast of the last recorded session, to recognize it when it's resumed
+    #[serde(default)]
+    pub last_video_id: Option<String>,
+}
+
+// The streaming session in progress
+struct OpenSession {
+    video_id: String,
+    start: DateTime<Utc>,
+    // Whether this continues the last recorded session (e.g. after a restart while live), so
+    // it isn't counted as another session
+    resumed: bool,
+}
+
+// The session in progress is kept here rather than in the polling loop so it can still be
+// recorded when the process is ended while live (Ctrl+C, logoff or shutdown).
+static OPEN_SESSION: Mutex<Option<OpenSession>> = Mutex::new(None);
+
+// Function to get the path to the stats file
+fn get_stats_path() -> std::result::Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_liveframe_dir()?.join("stats.json"))
+}
+
+// Loads the stats, starting from zero if the file doesn't exist yet
+pub fn load_stats() -> std::result::Result<Stats, Box<dyn std::error::Error>> {
+    let stats_path = get_stats_path()?;
+    if !stats_path.exists() {
+        return Ok(Stats::default());
+    }
+    let contents = fs::read_to_string(&stats_path)?;
+    Ok(serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse {}: {}", stats_path.display(), e))?)
+}
+
+// Marks the start of a streaming session of the broadcast `video_id`, which went live at
+// `start`. Time already recorded for the broadcast (before a restart) isn't counted again.
+pub fn start_session(video_id: &str, start: DateTime<Utc>) {
+    let stats = load_stats().unwrap_or_else(|e| {
+        warn!("{}", e);
+        Stats::default()
+    });
+    let (start, resumed) = resume_point(&stats, video_id, start);
+    *OPEN_SESSION.lock().unwrap_or_else(PoisonError::into_inner) = Some(OpenSession {
+        video_id: video_id.to_string(),
+        start,
+        resumed,
+    });
+}
+
+// Returns when the time of a session starting at `start` should be counted from, and whether
+// the session continues the last recorded one
+fn resume_point(stats: &Stats, video_id: &str, start: DateTime<Utc>) -> (DateTime<Utc>, bool) {
+    let start = match stats.last_session_end {
+        Some(last_session_end) => start.max(last_session_end),
+        None => start,
+    };
+    (start, stats.last_video_id.as_deref() == Some(video_id))
+}
+
+// Records the session in progress, if any, as ending at `end`
+pub fn end_session(end: DateTime<Utc>) {
+    let session = OPEN_SESSION
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    if let Some(session) = session {
+        record_session(session, end);
+    }
+}
+
+// Adds a finished streaming session to the running total
+fn record_session(session: OpenSession, end: DateTime<Utc>) {
+    let result = load_stats().and_then(|mut stats| {
+        let duration = add_session(&mut stats, session, end);
+        write_atomic(
+            &get_stats_path()?,
+            serde_json::to_string_pretty(&stats)?.as_bytes(),
+        )?;
+        info!(
+            "Streamed for {}, {} in total",
+            format_duration(duration),
+            format_duration(stats.total_streamed_secs)
+        );
+        Ok(())
+    });
+    if let Err(e) = result {
+        warn!(
+            "Failed to record the streaming session in stats.json: {}",
+            e
+        );
+    }
+}
+
+// Adds the session to the stats and returns its counted duration in seconds
+fn add_session(stats: &mut Stats, session: OpenSession, end: DateTime<Utc>) -> u64 {
+    let duration = (end - session.start).num_seconds().max(0) as u64;
+    stats.total_streamed_secs += duration;
+    if !session.resumed {
+        stats.sessions += 1;
+    }
+    stats.last_session_end = Some(end);
+    stats.last_video_id = Some(session.video_id);
+    duration
+}
+
+// Prints the accumulated streaming time for --stats
+pub fn print_stats() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let stats = load_stats()?;
+    println!(
+        "Total streamed: {} over {} session(s)",
+        format_duration(stats.total_streamed_secs),
+        stats.sessions
+    );
+    if let Some(last_session_end) = stats.last_session_end {
+        println!(
+            "Last stream ended: {}",
+            last_session_end
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+        );
+    }
+    Ok(())
+}
+
+fn format_duration(secs: u64) -> String {
+    format!("{}h {:02}m", secs / 3600, secs / 60 % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn open_session(stats: &Stats, video_id: &str, start: DateTime<Utc>) -> OpenSession {
+        let (start, resumed) = resume_point(stats, video_id, start);
+        OpenSession {
+            video_id: video_id.to_string(),
+            start,
+            resumed,
+        }
+    }
+
+    #[test]
+    fn restart_while_live_counts_the_stream_once() {
+        let mut stats = Stats::default();
+        let went_live = at("2024-05-01T12:00:00Z");
+
+        let session = open_session(&stats, "video", went_live);
+        add_session(&mut stats, session, at("2024-05-01T13:00:00Z"));
+        // Restarted while still live: the API reports the same start time again
+        let session = open_session(&stats, "video", went_live);
+        add_session(&mut stats, session, at("2024-05-01T13:30:00Z"));
+
+        assert_eq!(stats.total_streamed_secs, 90 * 60);
+        assert_eq!(stats.sessions, 1);
+    }
+
+    #[test]
+    fn new_broadcast_is_a_new_session() {
+        let mut stats = Stats::default();
+
+        let session = open_session(&stats, "first", at("2024-05-01T12:00:00Z"));
+        add_session(&mut stats, session, at("2024-05-01T13:00:00Z"));
+        let session = open_session(&stats, "second", at("2024-05-02T12:00:00Z"));
+        add_session(&mut stats, session, at("2024-05-02T12:30:00Z"));
+
+        assert_eq!(stats.total_streamed_secs, 90 * 60);
+        assert_eq!(stats.sessions, 2);
+        assert_eq!(stats.last_video_id.as_deref(), Some("second"));
+    }
+}
@@ -0,0 +1,29 @@
+use crate::models::StreamStatus;
+use crate::window;
+
+// What the main loop does to the frame window when the streaming status changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameAction {
+    // Switch to one of the window::COLOR_* color states
+    SetColorState(u8),
+    Show,
+}
+
+// Returns the frame actions for a change to `new_status`, in order. The color state is reset
+// first; a live stream keeps the unknown color until the audio task measures its loudness.
+// During quiet hours the frame isn't shown.
+pub fn status_change_actions(new_status: &StreamStatus, quiet: bool) -> Vec<FrameAction> {
+    let mut actions = vec![FrameAction::SetColorState(window::COLOR_UNKNOWN)];
+    let color_state = match new_status {
+        StreamStatus::Live(_) => None,
+        StreamStatus::PreShow => Some(window::COLOR_PRE_SHOW),
+        StreamStatus::Ready => Some(window::COLOR_READY),
+        StreamStatus::EncoderConnected => Some(window::COLOR_ENCODER_CONNECTED),
+        StreamStatus::Offline => Some(window::COLOR_IDLE),
+    };
+    actions.extend(color_state.map(FrameAction::SetColorState));
+    if !quiet {
+        actions.push(FrameAction::Show);
+    }
+    actions
+}
@@ -0,0 +1,178 @@
+use log::{debug, info, warn};
+use tokio::sync::Notify;
+use windows::{
+    core::w,
+    Win32::Foundation::{HWND, LPARAM, POINT, WPARAM},
+    Win32::UI::Shell::{
+        Shell_NotifyIconW, NIF_ICON, NIF_INFO, NIF_MESSAGE, NIF_TIP, NIIF_INFO, NIM_ADD,
+        NIM_DELETE, NIM_MODIFY, NOTIFYICONDATAW,
+    },
+    Win32::UI::WindowsAndMessaging::{
+        AppendMenuW, CreatePopupMenu, DestroyMenu, GetCursorPos, LoadIconW, PostMessageW,
+        SetForegroundWindow, TrackPopupMenu, IDI_APPLICATION, MF_STRING, TPM_RETURNCMD,
+        TPM_RIGHTBUTTON, WM_APP, WM_CONTEXTMENU, WM_NULL, WM_RBUTTONUP,
+    },
+};
+
+use crate::models::{AppState, LiveStream, StreamStatus};
+use crate::oauth;
+use crate::retry::RETRY_BUDGET;
+
+// The frame window owns a single tray icon
+const TRAY_ICON_ID: u32 = 1;
+
+// Sent to the frame window for mouse events on the tray icon
+pub const WM_APP_TRAY: u32 = WM_APP + 1;
+
+// Tray menu item IDs
+const ID_REFRESH_NOW: usize = 1;
+const ID_RELOAD_SECRETS: usize = 2;
+
+// Notified when "Refresh now" is picked from the tray menu, to poll without waiting for the
+// next interval
+pub static REFRESH_REQUESTED: Notify = Notify::const_new();
+
+fn notify_icon_data(hwnd: HWND) -> NOTIFYICONDATAW {
+    NOTIFYICONDATAW {
+        cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+        hWnd: hwnd,
+        uID: TRAY_ICON_ID,
+        ..Default::default()
+    }
+}
+
+// Copies the tooltip into the fixed-size, null-terminated buffer, truncating if needed
+fn set_tip(data: &mut NOTIFYICONDATAW, tooltip: &str) {
+    copy_wide(&mut data.szTip, tooltip);
+}
+
+// Copies text into a fixed-size, null-terminated UTF-16 buffer, truncating if needed
+fn copy_wide(buffer: &mut [u16], text: &str) {
+    let max = buffer.len() - 1;
+    for (slot, c) in buffer.iter_mut().zip(text.encode_utf16().take(max)) {
+        *slot = c;
+    }
+}
+
+// Builds the tooltip text from the current status. While polls are failing the status is
+// replaced with an error summary, since the last known status may be stale.
+pub fn tooltip_text(status: &StreamStatus, app_state: &AppState) -> String {
+    let status = match app_state.consecutive_errors {
+        _ if RETRY_BUDGET.is_exhausted() => "Retry budget exhausted, retrying paused".to_string(),
+        0 => match status {
+            StreamStatus::Live(LiveStream {
+                channel_name: Some(channel_name),
+                ..
+            }) => format!("Live on {}", channel_name),
+            _ => status.label().to_string(),
+        },
+        retries => format!("API error ({} retries)", retries),
+    };
+    match &app_state.last_error {
+        Some(error) => format!(
+            "liveframe: {}\nLast error at {}: {}",
+            status,
+            error.at.format("%H:%M:%S"),
+            error.message
+        ),
+        None => format!("liveframe: {}", status),
+    }
+}
+
+// Adds the tray icon for the frame window
+pub unsafe fn add_tray_icon(hwnd: HWND) {
+    let mut data = notify_icon_data(hwnd);
+    data.uFlags = NIF_ICON | NIF_TIP | NIF_MESSAGE;
+    data.uCallbackMessage = WM_APP_TRAY;
+    data.hIcon = LoadIconW(None, IDI_APPLICATION).unwrap_or_default();
+    set_tip(&mut data, "liveframe");
+
+    if Shell_NotifyIconW(NIM_ADD, &data).as_bool() {
+        debug!("Tray icon added");
+    } else {
+        warn!("Failed to add tray icon");
+    }
+}
+
+// Updates the tray icon tooltip. Safe to call from any thread.
+pub unsafe fn set_tray_tooltip(hwnd: HWND, tooltip: &str) {
+    if hwnd.0 == 0 {
+        return;
+    }
+    let mut data = notify_icon_data(hwnd);
+    data.uFlags = NIF_TIP;
+    set_tip(&mut data, tooltip);
+    Shell_NotifyIconW(NIM_MODIFY, &data);
+}
+
+// Shows a notification from the tray icon, which Windows 10 and later display as a toast
+pub unsafe fn show_notification(hwnd: HWND, title: &str, text: &str) {
+    let mut data = notify_icon_data(hwnd);
+    data.uFlags = NIF_INFO;
+    data.dwInfoFlags = NIIF_INFO;
+    copy_wide(&mut data.szInfoTitle, title);
+    copy_wide(&mut data.szInfo, text);
+    if !Shell_NotifyIconW(NIM_MODIFY, &data).as_bool() {
+        warn!("Failed to show notification: {}", text);
+    }
+}
+
+// Handles a WM_APP_TRAY message, showing the tray menu on right click
+pub unsafe fn handle_tray_message(hwnd: HWND, lparam: LPARAM) {
+    let event = lparam.0 as u32;
+    if event == WM_RBUTTONUP || event == WM_CONTEXTMENU {
+        show_tray_menu(hwnd);
+    }
+}
+
+unsafe fn show_tray_menu(hwnd: HWND) {
+    let menu = match CreatePopupMenu() {
+        Ok(menu) => menu,
+        Err(e) => {
+            warn!("Failed to create tray menu: {}", e);
+            return;
+        }
+    };
+    AppendMenuW(menu, MF_STRING, ID_REFRESH_NOW, w!("Refresh now"));
+    AppendMenuW(
+        menu,
+        MF_STRING,
+        ID_RELOAD_SECRETS,
+        w!("Reload client secrets"),
+    );
+
+    let mut cursor = POINT::default();
+    GetCursorPos(&mut cursor);
+    // The menu only closes on an outside click while its owner is in the foreground
+    SetForegroundWindow(hwnd);
+    let command = TrackPopupMenu(
+        menu,
+        TPM_RETURNCMD | TPM_RIGHTBUTTON,
+        cursor.x,
+        cursor.y,
+        0,
+        hwnd,
+        None,
+    );
+    PostMessageW(hwnd, WM_NULL, WPARAM(0), LPARAM(0));
+    DestroyMenu(menu);
+
+    match command.0 as usize {
+        ID_REFRESH_NOW => {
+            info!("Refresh requested from the tray menu");
+            REFRESH_REQUESTED.notify_one();
+        }
+        // For a replaced secret.json, without restarting liveframe
+        ID_RELOAD_SECRETS => {
+            info!("Client secrets will be read again for the next token refresh");
+            oauth::forget_client_secrets();
+        }
+        _ => {}
+    }
+}
+
+// Removes the tray icon so it doesn't linger after the window is destroyed
+pub unsafe fn remove_tray_icon(hwnd: HWND) {
+    let data = notify_icon_data(hwnd);
+    Shell_NotifyIconW(NIM_DELETE, &data);
+}
@@ -1,41 +1,208 @@
-use log::{debug, error, info};
-use std::sync::atomic::{AtomicU8, Ordering};
-use std::sync::mpsc;
+use chrono::Utc;
+use log::{debug, error, info, warn};
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
 use windows::{
     core::*,
     Win32::Foundation::*,
+    Win32::Graphics::Dwm::{
+        DwmSetWindowAttribute, DWMWA_BORDER_COLOR, DWMWA_WINDOW_CORNER_PREFERENCE, DWMWCP_ROUND,
+    },
     Win32::Graphics::Gdi::{
-        BeginPaint, CreateSolidBrush, DeleteObject, EndPaint, FillRect, InvalidateRect, PAINTSTRUCT,
+        BeginPaint, BitBlt, CombineRgn, CreateCompatibleBitmap, CreateCompatibleDC,
+        CreateRectRgnIndirect, DeleteDC, DeleteObject, EndPaint, ExcludeClipRect, GetDC, GetDIBits,
+        InvalidateRect, ReleaseDC, SelectObject, SetWindowRgn, BITMAPINFO, BITMAPINFOHEADER,
+        BI_RGB, CAPTUREBLT, DIB_RGB_COLORS, PAINTSTRUCT, RGN_DIFF, SRCCOPY,
     },
     Win32::System::LibraryLoader::GetModuleHandleW,
     Win32::UI::WindowsAndMessaging::*,
 };
 
+use crate::config::{Corner, FrameColors, FrameConfig, FrameStyle, RenderMode};
+use crate::diagnose;
+use crate::mirror::FrameMirror;
+use crate::renderer::renderer_for;
+use crate::stats;
+use crate::tray;
+
 // Frame color state, read by wndproc in the window thread and written by other tasks.
-// 0 = unknown (defaults to red), 1 = red (silent), 2 = green (audible), 3 = white (idle).
+// 0 = unknown (defaults to silent), 1 = silent, 2 = audible, 3 = idle, 4 = ready, 5 = pre-show,
+// 6 = encoder connected.
 pub const COLOR_UNKNOWN: u8 = 0;
-pub const COLOR_RED: u8 = 1;
-pub const COLOR_GREEN: u8 = 2;
-pub const COLOR_WHITE: u8 = 3;
+pub const COLOR_SILENT: u8 = 1;
+pub const COLOR_AUDIBLE: u8 = 2;
+pub const COLOR_IDLE: u8 = 3;
+pub const COLOR_READY: u8 = 4;
+pub const COLOR_PRE_SHOW: u8 = 5;
+pub const COLOR_ENCODER_CONNECTED: u8 = 6;
 
 static COLOR_STATE: AtomicU8 = AtomicU8::new(COLOR_UNKNOWN);
 
-// Updates the color state. If the category changed, invalidates the window so wndproc repaints.
-pub fn set_color_state(hwnd: HWND, new_state: u8) {
-    let prev = COLOR_STATE.swap(new_state, Ordering::Relaxed);
-    if prev != new_state && hwnd.0 != 0 {
-        unsafe {
-            InvalidateRect(hwnd, None, TRUE);
+// Whether detection is failing. Overrides the color state with the stale color, so the state
+// is still right once detection recovers.
+static STALE: AtomicBool = AtomicBool::new(false);
+// Palette slot of the stale color, after those of the color states
+const STALE_SLOT: usize = 7;
+
+// COLORREF (0x00BBGGRR) for each color state, indexed by the state value, then the stale color.
+// Defaults to red/red/green/white/dim yellow/azure/orange/amber until the main loop applies the
+// configured colors.
+static PALETTE: [AtomicU32; 8] = [
+    AtomicU32::new(0x0000FF),
+    AtomicU32::new(0x0000FF),
+    AtomicU32::new(0x00FF00),
+    AtomicU32::new(0xFFFFFF),
+    AtomicU32::new(0x008080),
+    AtomicU32::new(0xFF8000),
+    AtomicU32::new(0x0080FF),
+    AtomicU32::new(0x00C0FF),
+];
+
+thread_local! {
+    // Frame layout, owned by the window thread and read by wndproc
+    static FRAME_CONFIG: RefCell<FrameConfig> = RefCell::new(FrameConfig::default());
+    // Shared memory copy of the frame, when mirror_shared_memory is enabled
+    static FRAME_MIRROR: RefCell<Option<FrameMirror>> = const { RefCell::new(None) };
+    // Commands sent through the WindowHandle
+    static COMMANDS: RefCell<Option<mpsc::Receiver<WindowCommand>>> = const { RefCell::new(None) };
+    // Whether a WM_APP_COMMAND is already queued, shared with the WindowHandle
+    static COMMAND_PENDING: RefCell<Arc<AtomicBool>> = RefCell::new(Arc::default());
+    // Whether this is the main frame, which has the tray icon and records the session on logoff
+    static MAIN_WINDOW: Cell<bool> = const { Cell::new(true) };
+    // Color the frame is currently painted with, or is transitioning to
+    static FRAME_COLOR: Cell<COLORREF> = Cell::new(current_color());
+    // Color transition in progress towards FRAME_COLOR
+    static TRANSITION: Cell<Option<ColorTransition>> = const { Cell::new(None) };
+    // Whether the window region is in use, so it's rebuilt when the thickness changes
+    static REGION_APPLIED: Cell<bool> = const { Cell::new(false) };
+    // Whether the frame is drawn as a DWM window border instead of filled rectangles
+    static DWM_BORDER: Cell<bool> = const { Cell::new(false) };
+    // Whether snap_to_bounds is moving the window, so its own WM_MOVE/WM_SIZE are ignored
+    static SNAPPING: Cell<bool> = const { Cell::new(false) };
+    // The configured exclude_rects in client coordinates, clipped to the window
+    static EXCLUSIONS: RefCell<Vec<RECT>> = const { RefCell::new(Vec::new()) };
+}
+
+// Timer driving color transitions
+const TRANSITION_TIMER_ID: usize = 1;
+// Time between the steps of a color transition (about 60 steps per second)
+const TRANSITION_STEP_MS: u32 = 16;
+
+// A color transition, started from the color shown at the time
+#[derive(Clone, Copy)]
+struct ColorTransition {
+    from: COLORREF,
+    started: Instant,
+}
+
+// First Windows 11 build, which added DWMWA_BORDER_COLOR
+const DWM_BORDER_MIN_BUILD: u32 = 22000;
+
+// Commands handled by the window thread
+pub enum WindowCommand {
+    Show,
+    Hide,
+    SetColor(COLORREF),
+    SetThickness(u32),
+    SetTooltip(String),
+    // Shows a notification (title, text) from the tray icon
+    Notify(String, String),
+    Exit,
+}
+
+// Posted to the window thread when commands are queued
+const WM_APP_COMMAND: u32 = WM_APP;
+
+// How long to wait for a window thread to create its frame window
+const WINDOW_STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Join handle of a thread running a window message loop
+pub type WindowThread = thread::JoinHandle<Result<()>>;
+
+// What a frame window shows
+#[derive(Clone, Copy)]
+pub enum WindowRole {
+    // The main frame in the color of the current color state, with the tray icon
+    Main,
+    // An additional region in a fixed color
+    Region(COLORREF),
+}
+
+// Sends commands to the window thread
+#[derive(Clone)]
+pub struct WindowHandle {
+    // None for a detached handle, which drops all commands
+    hwnd: Option<HWND>,
+    tx: mpsc::Sender<WindowCommand>,
+    // Whether a WM_APP_COMMAND is already queued, so a burst of commands is handled (and
+    // repainted) at once
+    pending: Arc<AtomicBool>,
+}
+
+// HWND is not Send, but the handle is only used with PostMessageW and IsWindowVisible,
+// which may be called from any thread.
+unsafe impl Send for WindowHandle {}
+unsafe impl Sync for WindowHandle {}
+
+impl WindowHandle {
+    // Returns a handle without a window (--no-window). Commands sent to it are dropped, so no
+    // window APIs are called, except that notifications are logged instead.
+    pub fn detached() -> Self {
+        let (tx, _) = mpsc::channel();
+        Self {
+            hwnd: None,
+            tx,
+            pending: Arc::default(),
         }
     }
+
+    // Queues a command and wakes the window thread unless a wake-up is already pending
+    pub fn send(&self, command: WindowCommand) {
+        let Some(hwnd) = self.hwnd else {
+            // Without a tray icon the log is the only place a notification can be seen, so it
+            // is a warning to get through --quiet
+            if let WindowCommand::Notify(title, text) = command {
+                warn!("{}: {}", title, text);
+            }
+            return;
+        };
+        if self.tx.send(command).is_err() {
+            debug!("Window thread has exited, dropping command");
+            return;
+        }
+        if !self.pending.swap(true, Ordering::Relaxed) {
+            unsafe {
+                if !PostMessageW(hwnd, WM_APP_COMMAND, WPARAM(0), LPARAM(0)).as_bool() {
+                    self.pending.store(false, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    // Returns whether the window is currently visible
+    pub fn is_visible(&self) -> bool {
+        self.hwnd
+            .is_some_and(|hwnd| unsafe { IsWindowVisible(hwnd).as_bool() })
+    }
 }
 
-// Function to create window and run message loop in a separate thread
-pub unsafe fn create_window_and_run_message_loop(tx: mpsc::Sender<HWND>) -> Result<()> {
-    // Register the window class
+// Updates the color state. If the category changed, sends the new color to the window.
+pub fn set_color_state(window: &WindowHandle, new_state: u8) {
+    let prev = COLOR_STATE.swap(new_state, Ordering::Relaxed);
+    if prev != new_state {
+        window.send(WindowCommand::SetColor(current_color()));
+    }
+}
+
+const WINDOW_CLASS: PCWSTR = w!("RedFrameWindowClass");
+
+// Registers the frame window class. Registering an already registered class is harmless.
+unsafe fn register_window_class() -> Result<HMODULE> {
     debug!("Registering window class...");
     let instance = GetModuleHandleW(None)?;
-    let window_class = w!("RedFrameWindowClass");
 
     let wc = WNDCLASSEXW {
         cbSize: std::mem::size_of::<WNDCLASSEXW>() as u32,
@@ -43,50 +210,292 @@ pub unsafe fn create_window_and_run_message_loop(tx: mpsc::Sender<HWND>) -> Resu
         lpfnWndProc: Some(wndproc),
         hInstance: instance,
         hCursor: LoadCursorW(None, IDC_ARROW)?,
-        lpszClassName: window_class,
+        lpszClassName: WINDOW_CLASS,
         ..Default::default()
     };
 
     RegisterClassExW(&wc);
+    Ok(instance)
+}
 
+// Returns the bounds (x, y, width, height) of the frame window: the main display (without the
+// taskbar, with exclude_taskbar) or, for a corner badge, only its corner. The configured offsets
+// and size adjustments are applied last.
+unsafe fn frame_bounds(config: &FrameConfig) -> (i32, i32, i32, i32) {
     // Get the dimensions of the main display
-    let screen_width = GetSystemMetrics(SM_CXSCREEN);
-    let screen_height = GetSystemMetrics(SM_CYSCREEN);
-    debug!("Screen dimensions: {}x{}", screen_width, screen_height);
+    let mut screen = RECT {
+        left: 0,
+        top: 0,
+        right: GetSystemMetrics(SM_CXSCREEN),
+        bottom: GetSystemMetrics(SM_CYSCREEN),
+    };
+    if config.exclude_taskbar {
+        let mut work_area = RECT::default();
+        if SystemParametersInfoW(
+            SPI_GETWORKAREA,
+            0,
+            Some(&mut work_area as *mut RECT as *mut _),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+        .as_bool()
+        {
+            screen = work_area;
+        } else {
+            warn!(
+                "Failed to get the work area ({}), covering the taskbar",
+                Error::from_win32()
+            );
+        }
+    }
+    debug!(
+        "Screen area: ({}, {}) to ({}, {})",
+        screen.left, screen.top, screen.right, screen.bottom
+    );
+
+    let (x, y, width, height) = match config.style {
+        FrameStyle::Full | FrameStyle::CornerBrackets { .. } => (
+            screen.left,
+            screen.top,
+            screen.right - screen.left,
+            screen.bottom - screen.top,
+        ),
+        FrameStyle::CornerBadge { corner, size } => {
+            let size = size as i32;
+            let x = match corner {
+                Corner::TopLeft | Corner::BottomLeft => screen.left,
+                Corner::TopRight | Corner::BottomRight => screen.right - size,
+            };
+            let y = match corner {
+                Corner::TopLeft | Corner::TopRight => screen.top,
+                Corner::BottomLeft | Corner::BottomRight => screen.bottom - size,
+            };
+            (x, y, size, size)
+        }
+    };
+    (
+        x + config.x_offset,
+        y + config.y_offset,
+        (width + config.width_adjust).max(1),
+        (height + config.height_adjust).max(1),
+    )
+}
+
+// Creates the (hidden) frame window at frame_bounds. With `border`, the window gets a
+// non-client border for DWM to color.
+unsafe fn create_frame_window(instance: HMODULE, config: &FrameConfig, border: bool) -> HWND {
+    let (x, y, width, height) = frame_bounds(config);
+    let title_wide: Vec<u16> = config
+        .window_title
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+
+    let window_style = if border {
+        WS_POPUP | WS_BORDER
+    } else {
+        WS_POPUP
+    };
 
     // Create the window with the specified styles
-    info!("Creating frame window...");
-    let hwnd = CreateWindowExW(
+    CreateWindowExW(
         WS_EX_TOOLWINDOW | WS_EX_LAYERED | WS_EX_TOPMOST | WS_EX_NOACTIVATE,
-        window_class,
-        w!("Red Frame"),
-        WS_POPUP,
-        0,             // X position (at the left edge of the screen)
-        0,             // Y position (at the top edge of the screen)
-        screen_width,  // Width (screen width)
-        screen_height, // Height (screen height)
+        WINDOW_CLASS,
+        PCWSTR::from_raw(title_wide.as_ptr()),
+        window_style,
+        x,
+        y,
+        width,
+        height,
         None,
         None,
         instance,
         None,
-    );
+    )
+}
+
+// Creates the frame window and destroys it immediately, to check that window creation works
+pub unsafe fn test_window_creation() -> Result<()> {
+    let instance = register_window_class()?;
+    let hwnd = create_frame_window(instance, &FrameConfig::default(), false);
+    if hwnd.0 == 0 {
+        return Err(Error::from_win32());
+    }
+    DestroyWindow(hwnd);
+    Ok(())
+}
+
+// Updates the colors used for each state, sending the new color to the window if any of them
+// changed
+pub fn set_palette(window: &WindowHandle, colors: &FrameColors) {
+    let colors = [
+        colors.silent.to_colorref(),
+        colors.silent.to_colorref(),
+        colors.audible.to_colorref(),
+        colors.idle.to_colorref(),
+        colors.ready.to_colorref(),
+        colors.pre_show.to_colorref(),
+        colors.encoder_connected.to_colorref(),
+        colors.stale.to_colorref(),
+    ];
+    let mut changed = false;
+    for (slot, color) in PALETTE.iter().zip(colors) {
+        changed |= slot.swap(color, Ordering::Relaxed) != color;
+    }
+    if changed {
+        window.send(WindowCommand::SetColor(current_color()));
+    }
+}
+
+// Marks the streaming status as stale (or not), sending the new color to the window.
+// Returns whether it changed.
+pub fn set_stale(window: &WindowHandle, stale: bool) -> bool {
+    let changed = STALE.swap(stale, Ordering::Relaxed) != stale;
+    if changed {
+        window.send(WindowCommand::SetColor(current_color()));
+    }
+    changed
+}
+
+// Returns the color for the current color state
+pub fn current_color() -> COLORREF {
+    if STALE.load(Ordering::Relaxed) {
+        return COLORREF(PALETTE[STALE_SLOT].load(Ordering::Relaxed));
+    }
+    let state = COLOR_STATE.load(Ordering::Relaxed) as usize;
+    let slot = PALETTE
+        .get(state)
+        .unwrap_or(&PALETTE[COLOR_UNKNOWN as usize]);
+    COLORREF(slot.load(Ordering::Relaxed))
+}
+
+// Creates a frame window on a new thread running its message loop, and returns the window's
+// handle once it has been created
+pub fn spawn_frame_window(
+    frame_config: FrameConfig,
+    role: WindowRole,
+) -> Result<(WindowHandle, WindowThread)> {
+    // Create a channel for sending the window handle from the window thread to the caller
+    let (tx, rx) = mpsc::channel();
+    let window_thread = thread::spawn(move || unsafe {
+        create_window_and_run_message_loop(tx, frame_config, role)
+    });
+
+    // Wait to receive the window handle from the window thread
+    match rx.recv_timeout(WINDOW_STARTUP_TIMEOUT) {
+        Ok(handle) => Ok((handle, window_thread)),
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            error!(
+                "The window thread didn't create the frame window within {}s",
+                WINDOW_STARTUP_TIMEOUT.as_secs()
+            );
+            Err(Error::from_win32())
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            // The thread has exited (or panicked) without sending a handle
+            match window_thread.join() {
+                Ok(Err(e)) => error!("Failed to create the frame window: {}", e),
+                Ok(Ok(())) => error!("The window thread exited without creating the window"),
+                Err(_) => error!("The window thread panicked while creating the window"),
+            }
+            Err(Error::from_win32())
+        }
+    }
+}
+
+// Creates the frame window and runs its message loop on the current thread
+unsafe fn create_window_and_run_message_loop(
+    tx: mpsc::Sender<WindowHandle>,
+    frame_config: FrameConfig,
+    role: WindowRole,
+) -> Result<()> {
+    FRAME_CONFIG.with(|config| *config.borrow_mut() = frame_config);
+    if let WindowRole::Region(color) = role {
+        MAIN_WINDOW.with(|main| main.set(false));
+        FRAME_COLOR.with(|frame_color| frame_color.set(color));
+    }
+    let instance = register_window_class()?;
+
+    let dwm_border = FRAME_CONFIG.with(|config| config.borrow().render_mode)
+        == RenderMode::DwmBorder
+        && dwm_border_supported();
+
+    info!("Creating frame window...");
+    let hwnd =
+        FRAME_CONFIG.with(|config| create_frame_window(instance, &config.borrow(), dwm_border));
 
     if hwnd.0 == 0 {
         error!("Failed to create window");
         return Err(Error::from_win32());
     }
+    let exclusions = FRAME_CONFIG.with(|config| exclusion_rects(&config.borrow()));
+    EXCLUSIONS.with(|slot| *slot.borrow_mut() = exclusions);
 
     // Send the window handle to the main thread
-    if let Err(e) = tx.send(hwnd) {
+    let (command_tx, command_rx) = mpsc::channel();
+    COMMANDS.with(|commands| *commands.borrow_mut() = Some(command_rx));
+    let handle = WindowHandle {
+        hwnd: Some(hwnd),
+        tx: command_tx,
+        pending: COMMAND_PENDING.with(|pending| pending.borrow().clone()),
+    };
+    if let Err(e) = tx.send(handle) {
+        // Main has given up on the window, so don't leave it behind without a message loop
         error!("Failed to send window handle: {}", e);
-        return Err(Error::from_win32());
+        DestroyWindow(hwnd);
+        return Err(Error::new(
+            E_FAIL,
+            "main thread stopped waiting for the window".into(),
+        ));
     }
 
     // Set the window to be transparent except for the frame
     debug!("Setting window transparency...");
-    let color_key = COLORREF(0); // Black is transparent
-                                 // Use the full path for SetLayeredWindowAttributes
-    SetLayeredWindowAttributes(hwnd, color_key, 255, LWA_COLORKEY);
+    let opacity_percent = FRAME_CONFIG.with(|config| config.borrow().opacity_percent);
+    if let Err(e) = apply_transparency(hwnd, opacity_percent) {
+        error!(
+            "Failed to make the frame window transparent ({}); relying on the window region to \
+             keep the inside of the frame from being opaque black",
+            e
+        );
+    }
+
+    if dwm_border {
+        match apply_dwm_border(hwnd, FRAME_COLOR.with(Cell::get)) {
+            Ok(()) => DWM_BORDER.with(|enabled| enabled.set(true)),
+            Err(e) => {
+                error!(
+                    "Failed to color the DWM window border ({}); falling back to the filled frame",
+                    e
+                );
+                remove_window_border(hwnd);
+            }
+        }
+    }
+
+    // Clip the window to the frame strips, so the area inside the frame isn't part of the
+    // window at all and clicks there always reach the windows below. The DWM border is drawn
+    // around the whole window, so it keeps the full window.
+    if !DWM_BORDER.with(Cell::get) {
+        match apply_frame_region(hwnd) {
+            Ok(()) => REGION_APPLIED.with(|applied| applied.set(true)),
+            Err(e) => error!("Failed to set the frame window region: {}", e),
+        }
+    }
+
+    if MAIN_WINDOW.with(Cell::get)
+        && FRAME_CONFIG.with(|config| config.borrow().mirror_shared_memory)
+    {
+        let mut rect = RECT::default();
+        GetClientRect(hwnd, &mut rect);
+        match FrameMirror::create(rect.right, rect.bottom) {
+            Ok(mirror) => FRAME_MIRROR.with(|slot| *slot.borrow_mut() = Some(mirror)),
+            Err(e) => error!("Failed to create the shared memory frame mirror: {}", e),
+        }
+    }
+
+    if MAIN_WINDOW.with(Cell::get) {
+        tray::add_tray_icon(hwnd);
+    }
 
     // Message loop
     info!("Starting window message loop...");
@@ -100,6 +509,443 @@ pub unsafe fn create_window_and_run_message_loop(tx: mpsc::Sender<HWND>) -> Resu
     Ok(())
 }
 
+// Copies the top-left `width` x `height` area of the screen, including layered windows such as
+// the frame, and returns its BGRX pixels, top row first
+pub unsafe fn capture_screen(width: i32, height: i32) -> Result<Vec<u8>> {
+    let screen = GetDC(None);
+    if screen.0 == 0 {
+        return Err(Error::from_win32());
+    }
+    let memory = CreateCompatibleDC(screen);
+    let bitmap = CreateCompatibleBitmap(screen, width, height);
+    let previous = SelectObject(memory, bitmap);
+    // Without CAPTUREBLT, layered windows are left out of the copy
+    let copied = BitBlt(
+        memory,
+        0,
+        0,
+        width,
+        height,
+        screen,
+        0,
+        0,
+        SRCCOPY | CAPTUREBLT,
+    )
+    .as_bool();
+    SelectObject(memory, previous);
+
+    let mut info = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width,
+            // Negative for a top-down bitmap
+            biHeight: -height,
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0 as u32,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut pixels = vec![0u8; width.max(0) as usize * height.max(0) as usize * 4];
+    let lines = if copied {
+        GetDIBits(
+            memory,
+            bitmap,
+            0,
+            height as u32,
+            Some(pixels.as_mut_ptr().cast()),
+            &mut info,
+            DIB_RGB_COLORS,
+        )
+    } else {
+        0
+    };
+    // Read the error before the cleanup calls overwrite it
+    let error = (lines == 0).then(Error::from_win32);
+
+    DeleteObject(bitmap);
+    DeleteDC(memory);
+    ReleaseDC(None, screen);
+    if let Some(error) = error {
+        return Err(error);
+    }
+    Ok(pixels)
+}
+
+// Returns the color at (x, y) of pixels from capture_screen that are `width` pixels wide
+pub fn captured_color(pixels: &[u8], width: i32, x: i32, y: i32) -> COLORREF {
+    let offset = (y as usize * width as usize + x as usize) * 4;
+    let [blue, green, red] = [pixels[offset], pixels[offset + 1], pixels[offset + 2]];
+    COLORREF(red as u32 | (green as u32) << 8 | (blue as u32) << 16)
+}
+
+// Makes black pixels transparent via the layered window color key, and the rest of the window
+// translucent below 100% opacity, and verifies it took effect
+unsafe fn apply_transparency(hwnd: HWND, opacity_percent: u8) -> Result<()> {
+    let color_key = COLORREF(0); // Black is transparent
+    let alpha = (opacity_percent.min(100) as u32 * 255 / 100) as u8;
+    if !SetLayeredWindowAttributes(hwnd, color_key, alpha, LWA_COLORKEY | LWA_ALPHA).as_bool() {
+        return Err(Error::from_win32());
+    }
+
+    // Read the attributes back, since some environments accept the call but ignore it
+    let mut applied_key = COLORREF(0);
+    let mut applied_flags = LAYERED_WINDOW_ATTRIBUTES_FLAGS(0);
+    if !GetLayeredWindowAttributes(hwnd, Some(&mut applied_key), None, Some(&mut applied_flags))
+        .as_bool()
+    {
+        return Err(Error::from_win32());
+    }
+    if (applied_flags & LWA_COLORKEY) != LWA_COLORKEY || applied_key != color_key {
+        return Err(Error::new(
+            E_FAIL,
+            "color key was not applied to the layered window".into(),
+        ));
+    }
+
+    debug!("Window transparency applied");
+    Ok(())
+}
+
+// Returns whether DWM can color window borders, which needs Windows 11
+fn dwm_border_supported() -> bool {
+    match diagnose::real_os_version() {
+        Ok(info) if info.dwBuildNumber >= DWM_BORDER_MIN_BUILD => true,
+        Ok(info) => {
+            info!(
+                "DWM border coloring needs Windows 11 (build {}), this is build {}; \
+                 using the filled frame",
+                DWM_BORDER_MIN_BUILD, info.dwBuildNumber
+            );
+            false
+        }
+        Err(status) => {
+            error!(
+                "Failed to get the OS version ({:#x}); using the filled frame",
+                status
+            );
+            false
+        }
+    }
+}
+
+// Rounds the window corners and colors the DWM drawn window border
+unsafe fn apply_dwm_border(hwnd: HWND, color: COLORREF) -> Result<()> {
+    let corner = DWMWCP_ROUND;
+    DwmSetWindowAttribute(
+        hwnd,
+        DWMWA_WINDOW_CORNER_PREFERENCE,
+        &corner as *const _ as *const std::ffi::c_void,
+        std::mem::size_of_val(&corner) as u32,
+    )?;
+    set_dwm_border_color(hwnd, color)?;
+
+    debug!("DWM window border applied");
+    Ok(())
+}
+
+unsafe fn set_dwm_border_color(hwnd: HWND, color: COLORREF) -> Result<()> {
+    DwmSetWindowAttribute(
+        hwnd,
+        DWMWA_BORDER_COLOR,
+        &color as *const _ as *const std::ffi::c_void,
+        std::mem::size_of_val(&color) as u32,
+    )
+}
+
+// Sets the DWM border to the color shown right now, when the frame is drawn as the border
+unsafe fn apply_dwm_border_color(hwnd: HWND) {
+    if DWM_BORDER.with(Cell::get) {
+        if let Err(e) = set_dwm_border_color(hwnd, displayed_color()) {
+            error!("Failed to update the DWM border color: {}", e);
+        }
+    }
+}
+
+fn transition_duration() -> Duration {
+    Duration::from_millis(FRAME_CONFIG.with(|config| config.borrow().state_transition_ms))
+}
+
+// Starts a transition from `from` to FRAME_COLOR, replacing any transition in progress.
+// Without a transition duration the new color is shown right away.
+unsafe fn start_transition(hwnd: HWND, from: COLORREF) {
+    if transition_duration().is_zero() {
+        return;
+    }
+    TRANSITION.with(|transition| {
+        transition.set(Some(ColorTransition {
+            from,
+            started: Instant::now(),
+        }))
+    });
+    SetTimer(hwnd, TRANSITION_TIMER_ID, TRANSITION_STEP_MS, None);
+}
+
+// Returns the color the frame shows right now: FRAME_COLOR, or a color on the way to it while
+// a transition is in progress
+fn displayed_color() -> COLORREF {
+    let target = FRAME_COLOR.with(Cell::get);
+    let Some(transition) = TRANSITION.with(Cell::get) else {
+        return target;
+    };
+    let duration = transition_duration();
+    if duration.is_zero() {
+        return target;
+    }
+    let progress = transition.started.elapsed().as_secs_f32() / duration.as_secs_f32();
+    if progress >= 1.0 {
+        return target;
+    }
+    interpolate_hsv(transition.from, target, progress)
+}
+
+// Interpolates between two colors in HSV space, taking the shorter way around the hue circle
+fn interpolate_hsv(from: COLORREF, to: COLORREF, progress: f32) -> COLORREF {
+    let (mut from_h, from_s, from_v) = colorref_to_hsv(from);
+    let (mut to_h, to_s, to_v) = colorref_to_hsv(to);
+    // Grays have no hue, so take the other color's to avoid a detour through other hues
+    if from_s == 0.0 {
+        from_h = to_h;
+    } else if to_s == 0.0 {
+        to_h = from_h;
+    }
+
+    let mut hue_delta = to_h - from_h;
+    if hue_delta > 180.0 {
+        hue_delta -= 360.0;
+    } else if hue_delta < -180.0 {
+        hue_delta += 360.0;
+    }
+    hsv_to_colorref(
+        (from_h + hue_delta * progress).rem_euclid(360.0),
+        from_s + (to_s - from_s) * progress,
+        from_v + (to_v - from_v) * progress,
+    )
+}
+
+// Converts a COLORREF to hue (0-360), saturation and value (0-1)
+fn colorref_to_hsv(color: COLORREF) -> (f32, f32, f32) {
+    let r = (color.0 & 0xFF) as f32 / 255.0;
+    let g = ((color.0 >> 8) & 0xFF) as f32 / 255.0;
+    let b = ((color.0 >> 16) & 0xFF) as f32 / 255.0;
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    (hue, saturation, max)
+}
+
+fn hsv_to_colorref(hue: f32, saturation: f32, value: f32) -> COLORREF {
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    let channel = |c: f32| ((c + m) * 255.0).round().clamp(0.0, 255.0) as u32;
+    COLORREF(channel(b) << 16 | channel(g) << 8 | channel(r))
+}
+
+// Moves the window back to its bounds if something (e.g. an accessibility tool) moved or
+// resized it. The correction itself sends WM_MOVE/WM_SIZE again, which is ignored.
+unsafe fn snap_to_bounds(hwnd: HWND) {
+    if SNAPPING.with(Cell::get) {
+        return;
+    }
+
+    let (x, y, width, height) = FRAME_CONFIG.with(|config| frame_bounds(&config.borrow()));
+    let mut rect = RECT::default();
+    GetWindowRect(hwnd, &mut rect);
+    if (
+        rect.left,
+        rect.top,
+        rect.right - rect.left,
+        rect.bottom - rect.top,
+    ) == (x, y, width, height)
+    {
+        return;
+    }
+
+    warn!(
+        "Frame window was moved to ({}, {}) {}x{}, moving it back to ({}, {}) {}x{}",
+        rect.left,
+        rect.top,
+        rect.right - rect.left,
+        rect.bottom - rect.top,
+        x,
+        y,
+        width,
+        height
+    );
+    SNAPPING.with(|snapping| snapping.set(true));
+    SetWindowPos(
+        hwnd,
+        None,
+        x,
+        y,
+        width,
+        height,
+        SWP_NOZORDER | SWP_NOACTIVATE,
+    );
+    SNAPPING.with(|snapping| snapping.set(false));
+}
+
+// Moves the window to the new work area, rebuilding everything derived from its bounds
+unsafe fn fit_to_work_area(hwnd: HWND) {
+    info!("Work area changed, fitting the frame to it");
+    let exclusions = FRAME_CONFIG.with(|config| exclusion_rects(&config.borrow()));
+    EXCLUSIONS.with(|slot| *slot.borrow_mut() = exclusions);
+    snap_to_bounds(hwnd);
+    if REGION_APPLIED.with(Cell::get) {
+        if let Err(e) = apply_frame_region(hwnd) {
+            error!("Failed to update the frame window region: {}", e);
+        }
+    }
+    InvalidateRect(hwnd, None, TRUE);
+}
+
+// Drops the non-client border added for the DWM border, so the filled frame covers the edges
+unsafe fn remove_window_border(hwnd: HWND) {
+    let style = GetWindowLongPtrW(hwnd, GWL_STYLE);
+    SetWindowLongPtrW(hwnd, GWL_STYLE, style & !(WS_BORDER.0 as isize));
+    SetWindowPos(
+        hwnd,
+        None,
+        0,
+        0,
+        0,
+        0,
+        SWP_FRAMECHANGED | SWP_NOMOVE | SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE,
+    );
+}
+
+// Clips the window to the frame strips so nothing is drawn inside the frame,
+// which doesn't depend on layered window transparency
+unsafe fn apply_frame_region(hwnd: HWND) -> Result<()> {
+    let mut rect = RECT::default();
+    GetClientRect(hwnd, &mut rect);
+
+    let region = FRAME_CONFIG.with(|config| {
+        let config = config.borrow();
+        renderer_for(&config.style).region(&rect, &config)
+    });
+    EXCLUSIONS.with(|exclusions| {
+        for exclusion in exclusions.borrow().iter() {
+            let exclusion_region = CreateRectRgnIndirect(exclusion);
+            CombineRgn(region, region, exclusion_region, RGN_DIFF);
+            DeleteObject(exclusion_region);
+        }
+    });
+
+    // On success the system owns the region
+    if SetWindowRgn(hwnd, region, TRUE) == 0 {
+        DeleteObject(region);
+        return Err(Error::from_win32());
+    }
+
+    info!("Frame window region applied");
+    Ok(())
+}
+
+// Converts the configured exclude_rects to client coordinates of the frame window, clipped to
+// it. Rectangles that are empty or outside the window are dropped with a warning.
+unsafe fn exclusion_rects(config: &FrameConfig) -> Vec<RECT> {
+    let (x, y, width, height) = frame_bounds(config);
+    let mut rects = Vec::new();
+    for exclusion in &config.exclude_rects {
+        let rect = RECT {
+            left: (exclusion.x - x).max(0),
+            top: (exclusion.y - y).max(0),
+            right: (exclusion.x - x)
+                .saturating_add_unsigned(exclusion.width)
+                .min(width),
+            bottom: (exclusion.y - y)
+                .saturating_add_unsigned(exclusion.height)
+                .min(height),
+        };
+        if rect.left >= rect.right || rect.top >= rect.bottom {
+            warn!(
+                "Ignoring exclude rect {:?}, which doesn't overlap the frame window at ({}, {}) \
+                 {}x{}",
+                exclusion, x, y, width, height
+            );
+            continue;
+        }
+        rects.push(rect);
+    }
+    rects
+}
+
+// Renders the frame in the given color into a top-down BGRA buffer, independently of the
+// on-screen window. Pixels inside the frame are fully transparent. The buffer is resized to
+// fit, reusing its allocation.
+pub fn render_frame_to_buffer(
+    buffer: &mut Vec<u8>,
+    width: i32,
+    height: i32,
+    config: &FrameConfig,
+    color: COLORREF,
+) {
+    let (width, height) = (width.max(0), height.max(0));
+    buffer.clear();
+    buffer.resize(width as usize * height as usize * 4, 0);
+
+    let color = color.0;
+    let pixel = [
+        ((color >> 16) & 0xFF) as u8,
+        ((color >> 8) & 0xFF) as u8,
+        (color & 0xFF) as u8,
+        0xFF,
+    ];
+
+    let client = RECT {
+        left: 0,
+        top: 0,
+        right: width,
+        bottom: height,
+    };
+    for edge_rect in frame_rects(&client, config) {
+        for y in edge_rect.top.max(0)..edge_rect.bottom.min(height) {
+            for x in edge_rect.left.max(0)..edge_rect.right.min(width) {
+                let offset = (y as usize * width as usize + x as usize) * 4;
+                buffer[offset..offset + 4].copy_from_slice(&pixel);
+            }
+        }
+    }
+}
+
+// Makes the given rectangles of a buffer from render_frame_to_buffer transparent
+fn clear_rects(buffer: &mut [u8], width: i32, height: i32, rects: &[RECT]) {
+    let (width, height) = (width.max(0), height.max(0));
+    for rect in rects {
+        let (left, right) = (rect.left.clamp(0, width), rect.right.clamp(0, width));
+        for y in rect.top.max(0)..rect.bottom.min(height) {
+            let row = y as usize * width as usize;
+            buffer[(row + left as usize) * 4..(row + right.max(left) as usize) * 4].fill(0);
+        }
+    }
+}
+
+// Returns the rectangles covered by the configured frame style within the client area
+fn frame_rects(rect: &RECT, config: &FrameConfig) -> Vec<RECT> {
+    renderer_for(&config.style).rects(rect, config)
+}
+
 extern "system" fn wndproc(hwnd: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
     unsafe {
         match message {
@@ -111,54 +957,97 @@ extern "system" fn wndproc(hwnd: HWND, message: u32, wparam: WPARAM, lparam: LPA
                 let mut rect = RECT::default();
                 GetClientRect(hwnd, &mut rect);
 
-                // COLORREF is 0x00BBGGRR. Green when audio is audible, white when idle, red otherwise.
-                let color = match COLOR_STATE.load(Ordering::Relaxed) {
-                    COLOR_GREEN => COLORREF(0x00FF00),
-                    COLOR_WHITE => COLORREF(0xFFFFFF),
-                    _ => COLORREF(0x0000FF),
-                };
-                let brush = CreateSolidBrush(color);
-
-                let frame_thickness = 3;
-
-                let top_rect = RECT {
-                    left: 0,
-                    top: 0,
-                    right: rect.right,
-                    bottom: frame_thickness,
-                };
-                FillRect(hdc, &top_rect, brush);
-
-                let bottom_rect = RECT {
-                    left: 0,
-                    top: rect.bottom - frame_thickness,
-                    right: rect.right,
-                    bottom: rect.bottom,
-                };
-                FillRect(hdc, &bottom_rect, brush);
-
-                let left_rect = RECT {
-                    left: 0,
-                    top: 0,
-                    right: frame_thickness,
-                    bottom: rect.bottom,
-                };
-                FillRect(hdc, &left_rect, brush);
-
-                let right_rect = RECT {
-                    left: rect.right - frame_thickness,
-                    top: 0,
-                    right: rect.right,
-                    bottom: rect.bottom,
-                };
-                FillRect(hdc, &right_rect, brush);
-
-                DeleteObject(brush);
+                let color = displayed_color();
+
+                // Leave the excluded areas unpainted, in case the window region isn't applied
+                EXCLUSIONS.with(|exclusions| {
+                    for exclusion in exclusions.borrow().iter() {
+                        ExcludeClipRect(
+                            hdc,
+                            exclusion.left,
+                            exclusion.top,
+                            exclusion.right,
+                            exclusion.bottom,
+                        );
+                    }
+                });
+
+                // The DWM border is only one pixel wide, so the frame is also filled inside it
+                // to the configured thickness. DWM clips the fill to the rounded corners.
+                FRAME_CONFIG.with(|config| {
+                    let config = config.borrow();
+                    renderer_for(&config.style).paint(hdc, &rect, &config, color);
+                });
+
+                FRAME_MIRROR.with(|mirror| {
+                    if let Some(mirror) = mirror.borrow_mut().as_mut() {
+                        mirror.update(|pixels| {
+                            FRAME_CONFIG.with(|config| {
+                                render_frame_to_buffer(
+                                    pixels,
+                                    rect.right,
+                                    rect.bottom,
+                                    &config.borrow(),
+                                    color,
+                                )
+                            });
+                            EXCLUSIONS.with(|exclusions| {
+                                clear_rects(pixels, rect.right, rect.bottom, &exclusions.borrow())
+                            });
+                        });
+                    }
+                });
 
                 EndPaint(hwnd, &ps);
                 LRESULT(0)
             }
+            WM_TIMER if wparam.0 == TRANSITION_TIMER_ID => {
+                if TRANSITION
+                    .with(Cell::get)
+                    .is_none_or(|transition| transition.started.elapsed() >= transition_duration())
+                {
+                    TRANSITION.with(|transition| transition.set(None));
+                    KillTimer(hwnd, TRANSITION_TIMER_ID);
+                }
+                apply_dwm_border_color(hwnd);
+                InvalidateRect(hwnd, None, TRUE);
+                LRESULT(0)
+            }
+            WM_APP_COMMAND => {
+                handle_commands(hwnd);
+                LRESULT(0)
+            }
+            tray::WM_APP_TRAY => {
+                tray::handle_tray_message(hwnd, lparam);
+                LRESULT(0)
+            }
+            WM_MOVE | WM_SIZE => {
+                snap_to_bounds(hwnd);
+                LRESULT(0)
+            }
+            // The taskbar was moved, resized or set to auto-hide
+            WM_SETTINGCHANGE
+                if wparam.0 == SPI_SETWORKAREA.0 as usize
+                    && FRAME_CONFIG.with(|config| config.borrow().exclude_taskbar) =>
+            {
+                fit_to_work_area(hwnd);
+                LRESULT(0)
+            }
+            WM_QUERYENDSESSION => LRESULT(1),
+            // On logoff or shutdown the process is ended after this returns, without going
+            // through the Ctrl+C handler, so the streaming session is recorded here
+            WM_ENDSESSION => {
+                if wparam.0 != 0 && MAIN_WINDOW.with(Cell::get) {
+                    info!("Windows session is ending, saving state");
+                    stats::end_session(Utc::now());
+                    tray::remove_tray_icon(hwnd);
+                }
+                LRESULT(0)
+            }
             WM_DESTROY => {
+                if MAIN_WINDOW.with(Cell::get) {
+                    tray::remove_tray_icon(hwnd);
+                }
                 PostQuitMessage(0);
                 LRESULT(0)
             }
@@ -167,15 +1056,60 @@ extern "system" fn wndproc(hwnd: HWND, message: u32, wparam: WPARAM, lparam: LPA
     }
 }
 
-// Function to show or hide the window
-pub unsafe fn set_window_visibility(hwnd: HWND, visible: bool) {
-    if hwnd.0 != 0 {
-        if visible {
-            ShowWindow(hwnd, SW_SHOW);
-            info!("Window shown");
-        } else {
-            ShowWindow(hwnd, SW_HIDE);
-            info!("Window hidden");
+// Applies all queued commands, repainting once if the color or thickness changed
+unsafe fn handle_commands(hwnd: HWND) {
+    // Cleared first, so commands sent while draining post a new wake-up
+    COMMAND_PENDING.with(|pending| pending.borrow().store(false, Ordering::Relaxed));
+    let commands: Vec<_> = COMMANDS.with(|commands| match commands.borrow().as_ref() {
+        Some(rx) => rx.try_iter().collect(),
+        None => Vec::new(),
+    });
+
+    let mut repaint = false;
+    for command in commands {
+        match command {
+            WindowCommand::Show => {
+                ShowWindow(hwnd, SW_SHOW);
+                debug!("Window shown");
+            }
+            WindowCommand::Hide => {
+                ShowWindow(hwnd, SW_HIDE);
+                debug!("Window hidden");
+            }
+            WindowCommand::SetColor(color) => {
+                // A transition in progress continues from the color shown right now
+                let shown = displayed_color();
+                let changed = FRAME_COLOR.with(|current| current.replace(color)) != color;
+                if changed {
+                    start_transition(hwnd, shown);
+                    apply_dwm_border_color(hwnd);
+                }
+                repaint |= changed;
+            }
+            WindowCommand::SetThickness(thickness) => {
+                let previous = FRAME_CONFIG.with(|config| {
+                    std::mem::replace(&mut config.borrow_mut().thickness, thickness)
+                });
+                if previous != thickness {
+                    repaint = true;
+                    if REGION_APPLIED.with(Cell::get) {
+                        if let Err(e) = apply_frame_region(hwnd) {
+                            error!("Failed to update the frame window region: {}", e);
+                        }
+                    }
+                }
+            }
+            WindowCommand::SetTooltip(tooltip) => tray::set_tray_tooltip(hwnd, &tooltip),
+            WindowCommand::Notify(title, text) => tray::show_notification(hwnd, &title, &text),
+            WindowCommand::Exit => {
+                info!("Closing frame window...");
+                DestroyWindow(hwnd);
+                return;
+            }
         }
     }
+
+    if repaint {
+        InvalidateRect(hwnd, None, TRUE);
+    }
 }
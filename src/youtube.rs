@@ -1,50 +1,801 @@
-use log::{debug, error, info};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use regex::Regex;
 use reqwest;
 use serde_json;
 
-use crate::models::LiveBroadcastsResponse;
+use crate::config::{FallbackDetection, MultiBroadcastMode, TitleFilter, YouTubeConfig};
+use crate::models::{
+    LiveBroadcast, LiveBroadcastsResponse, LiveStream, LiveStreamsResponse, StreamStatus,
+    VideosResponse,
+};
+use crate::quota::QuotaKeyPool;
+
+// API calls slower than this are logged as warnings
+const SLOW_CALL_THRESHOLD: Duration = Duration::from_secs(5);
+
+// Number of recent API calls the average latency is computed over
+const LATENCY_WINDOW: usize = 20;
+
+// Time the secondary checks (live page fallback, ingest status) get before the poll goes on
+// without them, so a slow one doesn't hold up the status
+const SECONDARY_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Durations of the most recent API calls in milliseconds, oldest first
+static RECENT_LATENCIES_MS: Mutex<VecDeque<u64>> = Mutex::new(VecDeque::new());
 
-// Returns Some(video_id) when a live broadcast is active, None otherwise.
+// When the live page was last loaded, and the live stream it showed, if any
+static LAST_LIVE_PAGE: Mutex<Option<(Instant, Option<LiveStream>)>> = Mutex::new(None);
+
+// Returns the streaming status derived from the user's broadcasts.
 // The YouTube broadcast ID is identical to the video ID.
+// With `broadcast_index`, only the Nth of the live broadcasts is tracked.
 pub async fn check_youtube_streaming(
     access_token: &str,
-) -> std::result::Result<Option<String>, Box<dyn std::error::Error>> {
-    let client = reqwest::Client::new();
+    config: &YouTubeConfig,
+    key_pool: &mut QuotaKeyPool,
+) -> std::result::Result<StreamStatus, Box<dyn std::error::Error>> {
+    let client = api_client(config)?;
+    let title_filter = config.title_filter()?;
 
     debug!("Calling YouTube API to check streaming status...");
-    let response = client
-        .get("https://www.googleapis.com/youtube/v3/liveBroadcasts")
-        .query(&[("part", "id,snippet,status"), ("broadcastStatus", "active")])
-        .header("Authorization", format!("Bearer {}", access_token))
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        let error_text = response.text().await?;
-        error!("YouTube API returned error: {}", error_text);
-        return Err(format!("YouTube API error: {}", error_text).into());
-    }
+    let broadcasts = fetch_broadcasts(&client, access_token, key_pool, "active").await?;
 
-    let response_text = response.text().await?;
-    let broadcasts: LiveBroadcastsResponse = serde_json::from_str(&response_text)?;
+    info!("Found {} broadcasts", broadcasts.len());
 
-    info!("Found {} broadcasts", broadcasts.items.len());
-
-    for (i, broadcast) in broadcasts.items.iter().enumerate() {
+    for (i, broadcast) in broadcasts.iter().enumerate() {
         info!(
-            "Broadcast #{}: ID={}, Title={}, Status={:?}",
+            "Broadcast #{}: ID={}, Title={}, Status={:?}, Privacy={:?}",
             i + 1,
             broadcast.id,
             broadcast.snippet.title,
-            broadcast.status.life_cycle_status
+            broadcast.status.life_cycle_status,
+            broadcast.status.privacy_status
+        );
+    }
+
+    let (tracked, any_live) = track_broadcast(broadcasts, config, &title_filter);
+
+    if let Some(broadcast) = tracked {
+        return Ok(StreamStatus::Live(LiveStream {
+            thumbnail_url: broadcast.snippet.default_thumbnail_url(),
+            title: broadcast.snippet.title,
+            started_at: broadcast.snippet.actual_start_time,
+            channel_name: broadcast.snippet.channel_name,
+            video_id: broadcast.id,
+        }));
+    }
+
+    if !any_live && config.fallback_detection == FallbackDetection::LivePage {
+        match live_page_stream(&client, config).await {
+            Some(stream) if title_filter.matches(&stream.title) => {
+                info!(
+                    "Live page shows a live stream the API didn't list: ID={}, Title={}",
+                    stream.video_id, stream.title
+                );
+                return Ok(StreamStatus::Live(stream));
+            }
+            _ => debug!("Live page shows no (matching) live stream"),
+        }
+    }
+
+    // An encoder sending to an ingest point means a broadcast is about to go live
+    if config.show_encoder_indicator {
+        let ingest = tokio::time::timeout(
+            SECONDARY_CHECK_TIMEOUT,
+            get_ingest_status(&client, access_token, key_pool),
         );
+        match ingest.await {
+            Ok(Ok(true)) => return Ok(StreamStatus::EncoderConnected),
+            Ok(Ok(false)) => {}
+            // Not worth failing the whole poll over
+            Ok(Err(e)) => warn!("Failed to check the ingest status: {}", e),
+            Err(_) => warn!(
+                "The ingest status didn't arrive within {}s, skipping it",
+                SECONDARY_CHECK_TIMEOUT.as_secs()
+            ),
+        }
+    }
+
+    // Broadcasts that are set up but not started yet are only listed as upcoming
+    if config.show_ready_indicator || config.pre_show_mins > 0 {
+        let upcoming: Vec<_> = fetch_broadcasts(&client, access_token, key_pool, "upcoming")
+            .await?
+            .into_iter()
+            .filter(|b| title_matches(&title_filter, b))
+            .collect();
+
+        if config.show_ready_indicator {
+            if let Some(ready) = upcoming
+                .iter()
+                .find(|b| b.status.life_cycle_status.as_deref() == Some("ready"))
+            {
+                info!(
+                    "Broadcast is ready but not live: ID={}, Title={}",
+                    ready.id, ready.snippet.title
+                );
+                return Ok(StreamStatus::Ready);
+            }
+        }
+
+        if config.pre_show_mins > 0 {
+            let pre_show = chrono::Duration::minutes(config.pre_show_mins as i64);
+            if let Some(soon) = starting_soon(&upcoming, pre_show, Utc::now()) {
+                info!(
+                    "Broadcast starts soon: ID={}, Title={}, Scheduled={:?}",
+                    soon.id, soon.snippet.title, soon.snippet.scheduled_start_time
+                );
+                return Ok(StreamStatus::PreShow);
+            }
+        }
+    }
+
+    Ok(StreamStatus::Offline)
+}
+
+// Returns the first upcoming broadcast scheduled to start within `pre_show`. Broadcasts past
+// their scheduled start are still upcoming until they go live, so they count too.
+fn starting_soon(
+    upcoming: &[LiveBroadcast],
+    pre_show: chrono::Duration,
+    now: DateTime<Utc>,
+) -> Option<&LiveBroadcast> {
+    upcoming.iter().find(|b| {
+        b.snippet
+            .scheduled_start_time
+            .is_some_and(|start| start - now <= pre_show)
+    })
+}
+
+// Picks the live broadcast to track from the active ones, per the title filters,
+// multi_broadcast_mode and broadcast_index. Also returns whether any matching broadcast is live.
+fn track_broadcast(
+    broadcasts: Vec<LiveBroadcast>,
+    config: &YouTubeConfig,
+    title_filter: &TitleFilter,
+) -> (Option<LiveBroadcast>, bool) {
+    let matching: Vec<_> = broadcasts
+        .into_iter()
+        .filter(|b| title_matches(title_filter, b))
+        .collect();
+    let any_live = matching.iter().any(|b| b.status.is_live());
+
+    // Reduce the active broadcasts to the live ones that count, per multi_broadcast_mode
+    let live: Vec<_> = match &config.multi_broadcast_mode {
+        MultiBroadcastMode::Any => matching
+            .into_iter()
+            .filter(|b| b.status.is_live())
+            .collect(),
+        MultiBroadcastMode::All => {
+            let live_count = matching.iter().filter(|b| b.status.is_live()).count();
+            if live_count > 0 && live_count == matching.len() {
+                matching
+            } else {
+                if live_count > 0 {
+                    info!(
+                        "Only {} of {} active broadcasts are live, not treating the stream as live",
+                        live_count,
+                        matching.len()
+                    );
+                }
+                Vec::new()
+            }
+        }
+        MultiBroadcastMode::Specific { id } => matching
+            .into_iter()
+            .filter(|b| &b.id == id && b.status.is_live())
+            .collect(),
+    };
+
+    if !live.is_empty() {
+        info!(
+            "Live broadcasts matching multi_broadcast_mode {:?}: {}",
+            config.multi_broadcast_mode,
+            live.iter()
+                .map(|b| format!("{} ({})", b.id, b.snippet.title))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    let tracked = match config.broadcast_index {
+        Some(index) => {
+            let tracked = live.into_iter().nth(index);
+            if tracked.is_none() {
+                debug!("No live broadcast at broadcast_index {}", index);
+            }
+            tracked
+        }
+        None => live.into_iter().next(),
+    };
+    (tracked, any_live)
+}
+
+// The access token lacks a scope the request needs, so the user has to authorize again
+#[derive(Debug)]
+pub struct InsufficientScopeError(String);
+
+impl std::fmt::Display for InsufficientScopeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Token lacks a required scope: {}", self.0)
     }
+}
+
+impl std::error::Error for InsufficientScopeError {}
+
+// The API rejected a request for exceeding its rate limit (HTTP 429)
+#[derive(Debug)]
+pub struct RateLimitedError {
+    // Delay the API asked for in its Retry-After header, if any
+    pub retry_after: Option<Duration>,
+    message: String,
+}
+
+impl std::fmt::Display for RateLimitedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.retry_after {
+            Some(delay) => write!(
+                f,
+                "YouTube API rate limit exceeded, retry after {}s: {}",
+                delay.as_secs(),
+                self.message
+            ),
+            None => write!(f, "YouTube API rate limit exceeded: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for RateLimitedError {}
 
-    let video_id = broadcasts
+// Parses a Retry-After header value, either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    // A date in the past means retrying right away
+    Some(
+        (at.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+// Returns the user's broadcasts with the given broadcastStatus (active, upcoming, completed
+// or all), unfiltered
+pub async fn list_broadcasts(
+    access_token: &str,
+    config: &YouTubeConfig,
+    key_pool: &mut QuotaKeyPool,
+    broadcast_status: &str,
+) -> std::result::Result<Vec<LiveBroadcast>, Box<dyn std::error::Error>> {
+    let client = api_client(config)?;
+    fetch_broadcasts(&client, access_token, key_pool, broadcast_status).await
+}
+
+// Builds the HTTP client for API requests, identifying liveframe in the User-Agent header
+pub fn api_client(config: &YouTubeConfig) -> reqwest::Result<reqwest::Client> {
+    let user_agent = if config.user_agent.is_empty() {
+        concat!("liveframe/", env!("CARGO_PKG_VERSION")).to_string()
+    } else {
+        config.user_agent.clone()
+    };
+    reqwest::Client::builder().user_agent(user_agent).build()
+}
+
+// Returns the number of concurrent viewers of a live video, or None if it's hidden
+pub async fn fetch_concurrent_viewers(
+    access_token: &str,
+    config: &YouTubeConfig,
+    key_pool: &mut QuotaKeyPool,
+    video_id: &str,
+) -> std::result::Result<Option<u64>, Box<dyn std::error::Error>> {
+    let client = api_client(config)?;
+    let request = client
+        .get("https://www.googleapis.com/youtube/v3/videos")
+        .query(&[("part", "liveStreamingDetails"), ("id", video_id)])
+        .header("Authorization", format!("Bearer {}", access_token));
+    let videos: VideosResponse = serde_json::from_str(&send_keyed(request, key_pool).await?)?;
+    Ok(videos
         .items
         .into_iter()
-        .find(|b| b.status.life_cycle_status.as_deref() == Some("live"))
-        .map(|b| b.id);
+        .next()
+        .and_then(|video| video.live_streaming_details)
+        .and_then(|details| details.concurrent_viewers)
+        .and_then(|viewers| viewers.parse().ok()))
+}
+
+// Returns whether an encoder is sending to any of the user's ingest points (liveStreams)
+pub async fn get_ingest_status(
+    client: &reqwest::Client,
+    access_token: &str,
+    key_pool: &mut QuotaKeyPool,
+) -> std::result::Result<bool, Box<dyn std::error::Error>> {
+    let request = client
+        .get("https://www.googleapis.com/youtube/v3/liveStreams")
+        .query(&[
+            ("part", "id,status"),
+            ("mine", "true"),
+            ("maxResults", "50"),
+        ])
+        .header("Authorization", format!("Bearer {}", access_token));
+    let streams: LiveStreamsResponse = serde_json::from_str(&send_keyed(request, key_pool).await?)?;
+    let receiving = streams.items.iter().find(|stream| stream.is_receiving());
+    if let Some(stream) = receiving {
+        info!("Encoder is sending to ingest stream {}", stream.id);
+    }
+    Ok(receiving.is_some())
+}
+
+// Returns the live stream the channel's live page shows, loading the page at most once per
+// live_page_interval_secs and reusing the last result in between. A failed load counts as no
+// live stream, so the API result stands.
+async fn live_page_stream(client: &reqwest::Client, config: &YouTubeConfig) -> Option<LiveStream> {
+    let interval = Duration::from_secs(config.live_page_interval_secs);
+    let last = match LAST_LIVE_PAGE.lock() {
+        Ok(last) => last.clone(),
+        Err(_) => None,
+    };
+    if let Some((checked_at, stream)) = last {
+        if checked_at.elapsed() < interval {
+            debug!(
+                "Reusing the live page result from {:?} ago",
+                checked_at.elapsed()
+            );
+            return stream;
+        }
+    }
+
+    let live_page = tokio::time::timeout(
+        SECONDARY_CHECK_TIMEOUT,
+        check_live_page(client, &config.channel_id),
+    );
+    let stream = match live_page.await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            warn!("Failed to check the channel's live page: {}", e);
+            None
+        }
+        Err(_) => {
+            warn!(
+                "The channel's live page didn't respond within {}s, skipping it",
+                SECONDARY_CHECK_TIMEOUT.as_secs()
+            );
+            None
+        }
+    };
+    if let Ok(mut last) = LAST_LIVE_PAGE.lock() {
+        *last = Some((Instant::now(), stream.clone()));
+    }
+    stream
+}
+
+// Checks the channel's public /live page, which shows the current live video, if any.
+// Doesn't use API quota.
+async fn check_live_page(
+    client: &reqwest::Client,
+    channel_id: &str,
+) -> std::result::Result<Option<LiveStream>, Box<dyn std::error::Error>> {
+    if channel_id.is_empty() {
+        return Err("fallback_detection needs channel_id in [youtube]".into());
+    }
+
+    let url = format!("https://www.youtube.com/channel/{}/live", channel_id);
+    let response = client.get(&url).send().await?.error_for_status()?;
+    // In some regions (e.g. the EU), YouTube redirects cookieless requests to a consent form
+    // that never shows the live video
+    if response.url().host_str() == Some("consent.youtube.com") {
+        return Err(
+            "YouTube redirected to its cookie consent page, so the live page can't be \
+                    checked from this region"
+                .into(),
+        );
+    }
+    parse_live_page(&response.text().await?)
+}
+
+// Extracts the live video from the HTML of a channel's live page
+fn parse_live_page(
+    page: &str,
+) -> std::result::Result<Option<LiveStream>, Box<dyn std::error::Error>> {
+    // The page of an upcoming stream looks the same apart from isLiveNow
+    if !page.contains("\"isLiveNow\":true") {
+        return Ok(None);
+    }
+    let canonical =
+        Regex::new(r#"<link rel="canonical" href="https://www\.youtube\.com/watch\?v=([\w-]+)""#)?;
+    let Some(video_id) = canonical.captures(page).map(|c| c[1].to_string()) else {
+        return Ok(None);
+    };
+    let title = Regex::new(r#"<meta name="title" content="([^"]*)""#)?
+        .captures(page)
+        .map(|c| unescape_html(&c[1]))
+        .unwrap_or_default();
+
+    Ok(Some(LiveStream {
+        video_id,
+        title,
+        ..Default::default()
+    }))
+}
+
+// Decodes the character references in an HTML attribute value (e.g. &amp; and &#39;). Unknown
+// references are kept as they are.
+fn unescape_html(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').and_then(|end| {
+            let character = match &rest[1..end] {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                reference => reference
+                    .strip_prefix("#x")
+                    .or_else(|| reference.strip_prefix("#X"))
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| reference.strip_prefix('#').map(str::parse))
+                    .and_then(|code| code.ok())
+                    .and_then(char::from_u32),
+            };
+            character.map(|character| (character, end))
+        });
+        match decoded {
+            Some((character, end)) => {
+                unescaped.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+// Applies the configured title regexes, logging broadcasts that are filtered out
+fn title_matches(title_filter: &TitleFilter, broadcast: &LiveBroadcast) -> bool {
+    let matches = title_filter.matches(&broadcast.snippet.title);
+    if !matches {
+        debug!(
+            "Ignoring broadcast {} ({}) due to the title filters",
+            broadcast.id, broadcast.snippet.title
+        );
+    }
+    matches
+}
+
+// Fetches the user's broadcasts matching the given broadcastStatus filter, following pagination
+// so broadcasts past the first page (e.g. behind many scheduled ones) aren't missed
+async fn fetch_broadcasts(
+    client: &reqwest::Client,
+    access_token: &str,
+    key_pool: &mut QuotaKeyPool,
+    broadcast_status: &str,
+) -> std::result::Result<Vec<LiveBroadcast>, Box<dyn std::error::Error>> {
+    collect_pages(BroadcastPages {
+        client,
+        access_token,
+        key_pool,
+        broadcast_status,
+    })
+    .await
+}
+
+// Source of the pages of a liveBroadcasts.list response
+trait PageSource {
+    // Returns the body of the page with the given pageToken, or of the first page
+    async fn fetch_page(
+        &mut self,
+        page_token: Option<&str>,
+    ) -> std::result::Result<String, Box<dyn std::error::Error>>;
+}
+
+struct BroadcastPages<'a> {
+    client: &'a reqwest::Client,
+    access_token: &'a str,
+    key_pool: &'a mut QuotaKeyPool,
+    broadcast_status: &'a str,
+}
+
+impl PageSource for BroadcastPages<'_> {
+    async fn fetch_page(
+        &mut self,
+        page_token: Option<&str>,
+    ) -> std::result::Result<String, Box<dyn std::error::Error>> {
+        let mut request = self
+            .client
+            .get("https://www.googleapis.com/youtube/v3/liveBroadcasts")
+            .query(&[
+                ("part", "id,snippet,status"),
+                ("broadcastStatus", self.broadcast_status),
+                ("broadcastType", "all"),
+                ("maxResults", "50"),
+            ])
+            .header("Authorization", format!("Bearer {}", self.access_token));
+        if let Some(page_token) = page_token {
+            request = request.query(&[("pageToken", page_token)]);
+        }
+        send_keyed(request, self.key_pool).await
+    }
+}
+
+// Fetches pages until one comes without a nextPageToken, and returns the broadcasts of all
+async fn collect_pages(
+    mut pages: impl PageSource,
+) -> std::result::Result<Vec<LiveBroadcast>, Box<dyn std::error::Error>> {
+    let mut broadcasts = Vec::new();
+    let mut page_token: Option<String> = None;
+
+    loop {
+        let response_text = pages.fetch_page(page_token.as_deref()).await?;
+        let page: LiveBroadcastsResponse = serde_json::from_str(&response_text)?;
+        broadcasts.extend(page.items);
+
+        match page.next_page_token {
+            Some(next) => page_token = Some(next),
+            None => return Ok(broadcasts),
+        }
+    }
+}
+
+// Sends an API request with the current key of the pool, if any, and returns the response
+// body. A request that fails because the key is out of quota is retried with the next key.
+async fn send_keyed(
+    request: reqwest::RequestBuilder,
+    key_pool: &mut QuotaKeyPool,
+) -> std::result::Result<String, Box<dyn std::error::Error>> {
+    loop {
+        let mut keyed = request
+            .try_clone()
+            .ok_or("API request can't be retried with another key")?;
+        if let Some(key) = key_pool.current_key() {
+            keyed = keyed.query(&[("key", key)]);
+        }
+        let started = Instant::now();
+        let response = keyed.send().await?;
+        record_latency(started.elapsed(), response.status());
+
+        if response.status().is_success() {
+            return Ok(response.text().await?);
+        }
+
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+        let error_text = response.text().await?;
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(RateLimitedError {
+                retry_after,
+                message: error_text,
+            }
+            .into());
+        }
+        if status == reqwest::StatusCode::FORBIDDEN
+            && error_text.contains("quotaExceeded")
+            && key_pool.rotate()
+        {
+            continue;
+        }
+        if status == reqwest::StatusCode::FORBIDDEN
+            && error_text.contains("insufficientPermissions")
+        {
+            return Err(InsufficientScopeError(error_text).into());
+        }
+        error!("YouTube API returned error: {}", error_text);
+        return Err(format!("YouTube API error: {}", error_text).into());
+    }
+}
+
+// Logs the duration of an API call and the average over the recent calls
+fn record_latency(elapsed: Duration, status: reqwest::StatusCode) {
+    let elapsed_ms = elapsed.as_millis() as u64;
+    debug!("YouTube API call took {}ms, status {}", elapsed_ms, status);
+
+    let average_ms = match RECENT_LATENCIES_MS.lock() {
+        Ok(mut latencies) => {
+            if latencies.len() == LATENCY_WINDOW {
+                latencies.pop_front();
+            }
+            latencies.push_back(elapsed_ms);
+            latencies.iter().sum::<u64>() / latencies.len() as u64
+        }
+        Err(_) => elapsed_ms,
+    };
+
+    if elapsed >= SLOW_CALL_THRESHOLD {
+        warn!(
+            "YouTube API call took {}ms (average of the last {} calls: {}ms)",
+            elapsed_ms, LATENCY_WINDOW, average_ms
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Serves canned pages, keyed by the pageToken they're requested with
+    struct FakePages {
+        pages: Vec<(Option<&'static str>, &'static str)>,
+        requested: Vec<Option<String>>,
+    }
 
-    Ok(video_id)
+    impl PageSource for &mut FakePages {
+        async fn fetch_page(
+            &mut self,
+            page_token: Option<&str>,
+        ) -> std::result::Result<String, Box<dyn std::error::Error>> {
+            self.requested.push(page_token.map(str::to_string));
+            self.pages
+                .iter()
+                .find(|(token, _)| *token == page_token)
+                .map(|(_, page)| page.to_string())
+                .ok_or_else(|| format!("no page for {:?}", page_token).into())
+        }
+    }
+
+    fn parse_broadcasts(json: &str) -> Vec<LiveBroadcast> {
+        serde_json::from_str::<LiveBroadcastsResponse>(json)
+            .unwrap()
+            .items
+    }
+
+    #[tokio::test]
+    async fn pages_are_followed_to_the_last_one() {
+        let mut pages = FakePages {
+            pages: vec![
+                (
+                    None,
+                    r#"{"nextPageToken": "CAUQAA", "items": [
+                        {"id": "upcoming", "snippet": {"title": "Later"}, "status": {"lifeCycleStatus": "ready"}}
+                    ]}"#,
+                ),
+                (
+                    Some("CAUQAA"),
+                    r#"{"items": [
+                        {"id": "on-air", "snippet": {"title": "Now"}, "status": {"lifeCycleStatus": "live"}}
+                    ]}"#,
+                ),
+            ],
+            requested: Vec::new(),
+        };
+
+        let broadcasts = collect_pages(&mut pages).await.unwrap();
+        let ids: Vec<&str> = broadcasts.iter().map(|b| b.id.as_str()).collect();
+        assert_eq!(ids, ["upcoming", "on-air"]);
+        assert_eq!(pages.requested, [None, Some("CAUQAA".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn failed_page_fails_the_fetch() {
+        let mut pages = FakePages {
+            pages: vec![(None, r#"{"nextPageToken": "missing", "items": []}"#)],
+            requested: Vec::new(),
+        };
+
+        assert!(collect_pages(&mut pages).await.is_err());
+    }
+
+    #[test]
+    fn retry_after_seconds_are_parsed() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 0 "), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_http_date_is_parsed() {
+        let at = Utc::now() + chrono::Duration::hours(1);
+        let value = at.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+
+        let delay = parse_retry_after(&value).expect("an IMF-fixdate should parse");
+        assert!(
+            delay > Duration::from_secs(3590) && delay <= Duration::from_secs(3600),
+            "{:?}",
+            delay
+        );
+    }
+
+    #[test]
+    fn retry_after_past_date_retries_right_away() {
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn garbage_retry_after_is_ignored() {
+        assert_eq!(parse_retry_after("soon"), None);
+        assert_eq!(parse_retry_after("-5"), None);
+        assert_eq!(parse_retry_after(""), None);
+    }
+
+    #[test]
+    fn live_page_title_is_unescaped() {
+        let page = r#"<link rel="canonical" href="https://www.youtube.com/watch?v=dQw4w9WgXcQ">
+            <meta name="title" content="Q&amp;A &#39;stream&#x27; &lt;3 &bogus;">
+            {"isLiveNow":true}"#;
+
+        let stream = parse_live_page(page)
+            .unwrap()
+            .expect("the page shows a live stream");
+        assert_eq!(stream.video_id, "dQw4w9WgXcQ");
+        assert_eq!(stream.title, "Q&A 'stream' <3 &bogus;");
+    }
+
+    #[test]
+    fn upcoming_live_page_is_not_live() {
+        let page = r#"<link rel="canonical" href="https://www.youtube.com/watch?v=dQw4w9WgXcQ">
+            {"isLiveNow":false}"#;
+
+        assert!(parse_live_page(page).unwrap().is_none());
+    }
+
+    #[test]
+    fn overdue_broadcast_stays_in_pre_show() {
+        let upcoming = parse_broadcasts(
+            r#"{"items": [
+                {"id": "later", "snippet": {"title": "Later", "scheduledStartTime": "2024-05-01T14:00:00Z"}, "status": {"lifeCycleStatus": "ready"}},
+                {"id": "overdue", "snippet": {"title": "Overdue", "scheduledStartTime": "2024-05-01T11:50:00Z"}, "status": {"lifeCycleStatus": "ready"}}
+            ]}"#,
+        );
+        let now = "2024-05-01T12:00:00Z".parse().unwrap();
+        let pre_show = chrono::Duration::minutes(10);
+
+        let soon = starting_soon(&upcoming, pre_show, now).map(|b| b.id.as_str());
+        assert_eq!(soon, Some("overdue"));
+        assert!(starting_soon(&upcoming[..1], pre_show, now).is_none());
+    }
+
+    #[test]
+    fn membership_gated_live_broadcast_is_tracked() {
+        // Membership-only streams are listed as public with the usual lifecycle, sometimes
+        // without a title
+        let broadcasts = parse_broadcasts(
+            r#"{"items": [
+                {
+                    "id": "members-live",
+                    "snippet": {"channelTitle": "Channel", "actualStartTime": "2024-05-01T12:00:00Z"},
+                    "status": {"lifeCycleStatus": "live", "privacyStatus": "public"}
+                }
+            ]}"#,
+        );
+        let config = YouTubeConfig::default();
+
+        let (tracked, any_live) =
+            track_broadcast(broadcasts, &config, &config.title_filter().unwrap());
+        assert!(any_live);
+        let tracked = tracked.expect("the live broadcast should be tracked");
+        assert_eq!(tracked.id, "members-live");
+        assert_eq!(tracked.snippet.title, "");
+    }
+
+    #[test]
+    fn broadcast_without_life_cycle_status_is_not_tracked() {
+        let broadcasts = parse_broadcasts(
+            r#"{"items": [
+                {"id": "unknown", "snippet": {}, "status": {"privacyStatus": "unlisted"}}
+            ]}"#,
+        );
+        let config = YouTubeConfig::default();
+
+        let (tracked, any_live) =
+            track_broadcast(broadcasts, &config, &config.title_filter().unwrap());
+        assert!(tracked.is_none());
+        assert!(!any_live);
+    }
 }